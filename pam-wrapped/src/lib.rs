@@ -1,4 +1,6 @@
-use std::ffi::{c_int, CString, NulError};
+use std::ffi::{c_char, c_int, CString, NulError};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
 
 macro_rules! match_to_return_value {
     ($return_value:expr, $return_value_type:ty { $($const_value:ident => $variant:ident),* $(,)? }) => {{
@@ -100,6 +102,63 @@ pub unsafe fn pam_start(
     user: Option<&str>,
     pam_conversation: &pam_conv,
     pamh: &mut PamHandle,
+) -> Result<PamStartReturnValue, NulError> {
+    unsafe {
+        start_with(
+            service_name,
+            user,
+            pam_conversation,
+            pamh,
+            |service_name, user, pam_conversation, pamh| {
+                libpam_sys::pam_start(service_name, user, pam_conversation, pamh)
+            },
+        )
+    }
+}
+
+/// Start a PAM transaction that reads its configuration from `confdir` instead of `/etc/pam.d`
+///
+/// This is the same as [`pam_start`], except that the service files are looked up in `confdir`.
+/// This allows running against a configuration outside of `/etc`.
+pub unsafe fn pam_start_confdir(
+    service_name: &str,
+    user: Option<&str>,
+    pam_conversation: &pam_conv,
+    confdir: &Path,
+    pamh: &mut PamHandle,
+) -> Result<PamStartReturnValue, NulError> {
+    // NOTE: Linux-PAM copies the configuration directory into the handle, so it only has to live
+    // for the duration of the call.
+    let confdir = CString::new(confdir.as_os_str().as_bytes())?;
+
+    unsafe {
+        start_with(
+            service_name,
+            user,
+            pam_conversation,
+            pamh,
+            |service_name, user, pam_conversation, pamh| {
+                libpam_sys::pam_start_confdir(
+                    service_name,
+                    user,
+                    pam_conversation,
+                    confdir.as_ptr(),
+                    pamh,
+                )
+            },
+        )
+    }
+}
+
+/// Store the service name and user in `pamh` and start the transaction with `start`
+///
+/// The strings are kept in the handle, because Linux-PAM keeps pointers to them.
+unsafe fn start_with(
+    service_name: &str,
+    user: Option<&str>,
+    pam_conversation: &pam_conv,
+    pamh: &mut PamHandle,
+    start: impl FnOnce(*const c_char, *const c_char, *const pam_conv, *mut *mut pam_handle_t) -> c_int,
 ) -> Result<PamStartReturnValue, NulError> {
     let service_name = CString::new(service_name)?;
     let service_name_ptr = service_name.as_ptr();
@@ -109,7 +168,7 @@ pub unsafe fn pam_start(
     } else {
         None
     };
-    let user_ptr = user.map_or(std::ptr::null(), |user| user.as_ptr());
+    let user_ptr = user.as_ref().map_or(std::ptr::null(), |user| user.as_ptr());
 
     pamh.service_name = service_name;
     pamh.user = user;
@@ -117,8 +176,7 @@ pub unsafe fn pam_start(
     let pam_conversation = pam_conversation as *const pam_conv;
     let pamh = (&mut pamh.raw_handle) as *mut *mut pam_handle_t;
 
-    let return_value =
-        unsafe { libpam_sys::pam_start(service_name_ptr, user_ptr, pam_conversation, pamh) };
+    let return_value = start(service_name_ptr, user_ptr, pam_conversation, pamh);
 
     Ok(match_to_return_value! {
         return_value, PamStartReturnValue {
@@ -1,4 +1,4 @@
-use pamela::PamConfig;
+use pamela::{PamConfig, RuleContent};
 
 fn main() {
     let config = PamConfig::from_system().expect("Failed to parse pam configuration");
@@ -6,7 +6,18 @@ fn main() {
     for service in config.services() {
         println!("Service '{}':", service.name());
         for rule in service.rules() {
-            println!("    '{}' '{}'", rule.domain(), rule.module_path());
+            match (rule.domain(), rule.content()) {
+                (Some(domain), RuleContent::Entry { module_path, .. }) => {
+                    println!("    '{}' '{}'", domain, module_path)
+                }
+                (Some(domain), RuleContent::ServiceInclusion { method, service }) => {
+                    println!("    '{}' '{}' '{}'", domain, method, service)
+                }
+                (None, _) => println!(
+                    "    '@include' '{}'",
+                    rule.included_service().unwrap_or_default()
+                ),
+            }
         }
     }
 }
@@ -0,0 +1,327 @@
+//! Borrowed variants of the rule types that point into the source they were parsed from
+//!
+//! Parsing into a [`PamRule`] allocates for every service name, module path and module argument.
+//! A [`PamRuleRef`] borrows all of them from the source instead, and only allocates when an
+//! escape sequence has to be removed: a `\]` within brackets or a backslash-newline line
+//! continuation. It converts to a [`PamRule`] on demand with [`PamRuleRef::into_owned`].
+
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::parsing::EscapedLines;
+use crate::{
+    take_packed_rule, take_rule, Control, Domain, InclusionMethod, ModuleArgumentRef, ModulePath,
+    PamConfigSyntaxError, PamRule, RuleContent, Span,
+};
+
+/// Borrowed variant of a [`PamRule`]
+#[derive(Debug)]
+pub struct PamRuleRef<'a> {
+    pub(crate) span: Span,
+    pub(crate) is_logging_enabled: bool,
+    pub(crate) domain: Option<Domain>,
+    pub(crate) content: RuleContentRef<'a>,
+}
+
+/// Borrowed variant of a [`RuleContent`]
+#[derive(Debug)]
+pub enum RuleContentRef<'a> {
+    /// Use the rules of another service
+    ServiceInclusion {
+        method: InclusionMethod,
+        service: Cow<'a, str>,
+    },
+    /// Call a module
+    Entry {
+        control: Control,
+        module_path: Cow<'a, str>,
+        module_arguments: Vec<ModuleArgumentRef<'a>>,
+    },
+}
+
+/// Iterator over the packed configuration format that yields [`PamRuleRef`]s
+///
+/// See [`PackedRuleIterator`](crate::PackedRuleIterator) for the syntax.
+pub struct PackedRuleRefIterator<'a>(EscapedLines<'a>);
+
+/// Iterator over the separated configuration format that yields [`PamRuleRef`]s
+///
+/// See [`SeparatedRuleIterator`](crate::SeparatedRuleIterator) for the syntax.
+pub struct SeparatedRuleRefIterator<'a>(EscapedLines<'a>);
+
+impl<'a> PamRuleRef<'a> {
+    /// Create an iterator over borrowed rules in the packed configuration format
+    ///
+    /// This is the same as [`PamRule::packed_iter`], except that the rules and service names
+    /// borrow from `s`.
+    pub fn packed_iter(s: &'a str) -> PackedRuleRefIterator<'a> {
+        PackedRuleRefIterator(EscapedLines::new(s, None))
+    }
+
+    /// Create an iterator over borrowed rules in the separated configuration format
+    ///
+    /// This is the same as [`PamRule::separated_iter`], except that the rules borrow from `s`.
+    pub fn separated_iter(s: &'a str) -> SeparatedRuleRefIterator<'a> {
+        SeparatedRuleRefIterator(EscapedLines::new(s, None))
+    }
+
+    /// Create an iterator over borrowed rules of a service file that was read from `path`
+    ///
+    /// The `path` only ends up in the [`Span`]s of the rules.
+    pub fn separated_iter_with_path(
+        s: &'a str,
+        path: impl AsRef<Path>,
+    ) -> SeparatedRuleRefIterator<'a> {
+        SeparatedRuleRefIterator(EscapedLines::new(s, Some(Arc::from(path.as_ref()))))
+    }
+
+    /// Get the location of this rule in its source
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Get whether to log for this rule or not
+    pub fn is_logging_enabled(&self) -> bool {
+        self.is_logging_enabled
+    }
+
+    /// Get the [`Domain`], which is `None` for an `@include` directive
+    pub fn domain(&self) -> Option<Domain> {
+        self.domain
+    }
+
+    /// Get what this rule does
+    pub fn content(&self) -> &RuleContentRef<'a> {
+        &self.content
+    }
+
+    /// Get the name of the service this rule includes, which is `None` for a module rule
+    pub fn included_service(&self) -> Option<&str> {
+        match &self.content {
+            RuleContentRef::ServiceInclusion { service, .. } => Some(service),
+            RuleContentRef::Entry { .. } => None,
+        }
+    }
+
+    /// Get the control parameters, which is `None` for a rule that includes another service
+    pub fn control(&self) -> Option<&Control> {
+        match &self.content {
+            RuleContentRef::Entry { control, .. } => Some(control),
+            RuleContentRef::ServiceInclusion { .. } => None,
+        }
+    }
+
+    /// Get the module path, which is `None` for a rule that includes another service
+    pub fn module_path(&self) -> Option<&str> {
+        match &self.content {
+            RuleContentRef::Entry { module_path, .. } => Some(module_path),
+            RuleContentRef::ServiceInclusion { .. } => None,
+        }
+    }
+
+    /// Get the module arguments, which is empty for a rule that includes another service
+    pub fn module_arguments(&self) -> &[ModuleArgumentRef<'a>] {
+        match &self.content {
+            RuleContentRef::Entry {
+                module_arguments, ..
+            } => module_arguments,
+            RuleContentRef::ServiceInclusion { .. } => &[],
+        }
+    }
+
+    /// Convert into a [`PamRule`], which only allocates for borrowed parts
+    pub fn into_owned(self) -> PamRule {
+        let content = match self.content {
+            RuleContentRef::ServiceInclusion { method, service } => RuleContent::ServiceInclusion {
+                method,
+                service: service.into_owned(),
+            },
+            RuleContentRef::Entry {
+                control,
+                module_path,
+                module_arguments,
+            } => RuleContent::Entry {
+                control,
+                module_path: ModulePath::new_unchecked(module_path.into_owned()),
+                module_arguments: module_arguments
+                    .into_iter()
+                    .map(ModuleArgumentRef::into_owned)
+                    .collect(),
+            },
+        };
+
+        PamRule {
+            span: self.span,
+            is_logging_enabled: self.is_logging_enabled,
+            domain: self.domain,
+            content,
+        }
+    }
+
+    /// Convert all borrowed parts into owned ones, so that the rule no longer borrows from the
+    /// source
+    pub fn into_static(self) -> PamRuleRef<'static> {
+        let content = match self.content {
+            RuleContentRef::ServiceInclusion { method, service } => {
+                RuleContentRef::ServiceInclusion {
+                    method,
+                    service: Cow::Owned(service.into_owned()),
+                }
+            }
+            RuleContentRef::Entry {
+                control,
+                module_path,
+                module_arguments,
+            } => RuleContentRef::Entry {
+                control,
+                module_path: Cow::Owned(module_path.into_owned()),
+                module_arguments: module_arguments
+                    .into_iter()
+                    .map(ModuleArgumentRef::into_static)
+                    .collect(),
+            },
+        };
+
+        PamRuleRef {
+            span: self.span,
+            is_logging_enabled: self.is_logging_enabled,
+            domain: self.domain,
+            content,
+        }
+    }
+}
+
+impl From<PamRuleRef<'_>> for PamRule {
+    fn from(value: PamRuleRef<'_>) -> Self {
+        value.into_owned()
+    }
+}
+
+impl<'a> Iterator for PackedRuleRefIterator<'a> {
+    type Item = Result<(Cow<'a, str>, PamRuleRef<'a>), PamConfigSyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let escaped_line = self.0.next()?;
+
+        // A line with a line continuation is owned, so it cannot be borrowed from
+        Some(match &escaped_line.text {
+            Cow::Borrowed(text) => take_packed_rule(&escaped_line, text),
+            Cow::Owned(text) => take_packed_rule(&escaped_line, text)
+                .map(|(name, rule)| (Cow::Owned(name.into_owned()), rule.into_static())),
+        })
+    }
+}
+
+impl<'a> Iterator for SeparatedRuleRefIterator<'a> {
+    type Item = Result<PamRuleRef<'a>, PamConfigSyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let escaped_line = self.0.next()?;
+
+        // A line with a line continuation is owned, so it cannot be borrowed from
+        Some(match &escaped_line.text {
+            Cow::Borrowed(text) => take_rule(&escaped_line, text),
+            Cow::Owned(text) => take_rule(&escaped_line, text).map(PamRuleRef::into_static),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ModuleArgument, PamService};
+
+    #[test]
+    fn borrowed() {
+        let source = "auth [success=1 default=ignore] pam_unix.so nullok [foo=a b]\n\
+                      @include common-auth\n";
+        let rules = PamRuleRef::separated_iter(source)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let RuleContentRef::Entry {
+            module_path,
+            module_arguments,
+            ..
+        } = rules[0].content()
+        else {
+            panic!("Expected an entry");
+        };
+        assert!(matches!(module_path, Cow::Borrowed(_)));
+        assert!(matches!(
+            &module_arguments[0],
+            ModuleArgumentRef::Set(Cow::Borrowed("nullok"))
+        ));
+        assert!(matches!(
+            &module_arguments[1],
+            ModuleArgumentRef::KeyValue {
+                key: Cow::Borrowed("foo"),
+                value: Cow::Borrowed("a b"),
+            }
+        ));
+
+        let RuleContentRef::ServiceInclusion { service, .. } = rules[1].content() else {
+            panic!("Expected an inclusion");
+        };
+        assert!(matches!(service, Cow::Borrowed(_)));
+
+        let (name, _) = PamRuleRef::packed_iter("login auth required pam_unix.so\n")
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(name, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn escapes() {
+        let source = "auth required pam_unix.so [foo=a\\]b] nullok\n\
+                      auth required \\\n    pam_env.so\n";
+        let rules = PamRuleRef::separated_iter(source)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert!(matches!(
+            &rules[0].module_arguments()[0],
+            ModuleArgumentRef::KeyValue {
+                key: Cow::Owned(key),
+                value: Cow::Owned(value),
+            } if key == "foo" && value == "a]b"
+        ));
+        assert!(matches!(
+            &rules[0].module_arguments()[1],
+            ModuleArgumentRef::Set(Cow::Borrowed("nullok"))
+        ));
+
+        let RuleContentRef::Entry { module_path, .. } = rules[1].content() else {
+            panic!("Expected an entry");
+        };
+        assert_eq!(module_path, "pam_env.so");
+        assert!(matches!(module_path, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn into_owned() {
+        let source = "-auth [success=1 default=ignore] pam_unix.so nullok [foo=a\\]b]\n\
+                      account include system-account\n";
+        let owned = PamService::from_str(source).unwrap();
+        let borrowed: Vec<PamRule> = PamRuleRef::separated_iter(source)
+            .map(|rule| rule.unwrap().into_owned())
+            .collect();
+
+        assert_eq!(borrowed.len(), owned.len());
+        for (borrowed, owned) in borrowed.iter().zip(&owned) {
+            assert_eq!(borrowed.to_string(), owned.to_string());
+            assert_eq!(borrowed.span(), owned.span());
+        }
+        assert!(matches!(
+            &borrowed[0].module_arguments()[1],
+            ModuleArgument::KeyValue { key, value } if key == "foo" && value == "a]b"
+        ));
+
+        let errors: Vec<_> = PamRuleRef::separated_iter("auth required pam_unix.so =x\n")
+            .filter_map(Result::err)
+            .collect();
+        assert_eq!(errors[0].to_string(), "1:27: invalid module argument `=x`");
+    }
+}
@@ -0,0 +1,413 @@
+//! Builders for [`PamRule`]s and [`Selection`]s
+//!
+//! Everything that a builder accepts can be written by the [`PamWriter`](crate::PamWriter) and
+//! parsed back into the same rule.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::parsing::is_escapable;
+use crate::{
+    Action, Control, Domain, InclusionMethod, ModuleArgument, ModulePath, PamRule, RuleContent,
+    Selection, SelectionItem, Span, Value,
+};
+
+/// Builder for a [`PamRule`], created with [`PamRule::builder`]
+///
+/// # Examples
+///
+/// ```
+/// use pamela::{Control, Domain, PamRule};
+///
+/// let rule = PamRule::builder(Domain::Auth)
+///     .control(Control::Required)
+///     .module("pam_unix.so")
+///     .arg("try_first_pass")
+///     .build()?;
+///
+/// assert_eq!(rule.to_string(), "auth required pam_unix.so try_first_pass");
+/// # Ok::<(), pamela::BuildError>(())
+/// ```
+#[derive(Debug)]
+pub struct PamRuleBuilder {
+    domain: Domain,
+    is_logging_enabled: bool,
+    control: Option<Control>,
+    module: Option<String>,
+    arguments: Vec<String>,
+    inclusion: Option<(InclusionMethod, String)>,
+}
+
+/// Builder for a [`Selection`], created with [`Selection::builder`]
+///
+/// # Examples
+///
+/// ```
+/// use pamela::{Action, ReturnCode, Selection, Value};
+///
+/// let selection = Selection::builder()
+///     .item(ReturnCode::Success, Action::JumpOver(2))
+///     .item(Value::Default, Action::Ignore)
+///     .build()?;
+///
+/// assert_eq!(selection.to_string(), "[success=2 default=ignore]");
+/// # Ok::<(), pamela::BuildError>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct SelectionBuilder {
+    items: Vec<SelectionItem>,
+}
+
+/// Invalid input to a [`PamRuleBuilder`] or [`SelectionBuilder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// A module rule has no control
+    MissingControl,
+    /// A module rule has no module
+    MissingModule,
+    /// A control that Linux-PAM does not know, such as the OpenPAM `binding`
+    UnsupportedControl(Control),
+    /// A rule both includes a service and calls a module
+    ConflictingContent,
+    InvalidModulePath(String),
+    InvalidArgument(String),
+    InvalidService(String),
+    /// A selection has no items
+    EmptySelection,
+    /// A selection has more than one item for the same value
+    DuplicateValue(Value),
+    /// A selection jumps over zero rules
+    ZeroJump,
+}
+
+impl PamRule {
+    /// Create a builder for a rule in `domain`
+    pub fn builder(domain: Domain) -> PamRuleBuilder {
+        PamRuleBuilder {
+            domain,
+            is_logging_enabled: true,
+            control: None,
+            module: None,
+            arguments: Vec::new(),
+            inclusion: None,
+        }
+    }
+
+    /// Create a Debian-style `@include` directive, which has no domain
+    pub fn include_directive(service: &str) -> Result<PamRule, BuildError> {
+        validate_service(service)?;
+
+        Ok(PamRule {
+            span: Span::detached(),
+            is_logging_enabled: true,
+            domain: None,
+            content: RuleContent::ServiceInclusion {
+                method: InclusionMethod::IncludeDirective,
+                service: service.to_string(),
+            },
+        })
+    }
+}
+
+impl PamRuleBuilder {
+    /// Set the control of a module rule
+    pub fn control(mut self, control: impl Into<Control>) -> Self {
+        self.control = Some(control.into());
+        self
+    }
+
+    /// Set the path of the module, either relative to the module directory or absolute
+    pub fn module(mut self, module: impl Into<String>) -> Self {
+        self.module = Some(module.into());
+        self
+    }
+
+    /// Append a module argument, e.g. `nullok` or `deny=3`
+    pub fn arg(mut self, argument: impl Into<String>) -> Self {
+        self.arguments.push(argument.into());
+        self
+    }
+
+    /// Append several module arguments
+    pub fn args(mut self, arguments: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.arguments.extend(arguments.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set whether a missing module is logged, which is disabled by a `-` in front of the domain
+    pub fn logging(mut self, is_logging_enabled: bool) -> Self {
+        self.is_logging_enabled = is_logging_enabled;
+        self
+    }
+
+    /// Use the rules of `service` with an `include` control instead of calling a module
+    pub fn include(mut self, service: impl Into<String>) -> Self {
+        self.inclusion = Some((InclusionMethod::Include, service.into()));
+        self
+    }
+
+    /// Run the rules of `service` as a `substack` instead of calling a module
+    pub fn substack(mut self, service: impl Into<String>) -> Self {
+        self.inclusion = Some((InclusionMethod::Substack, service.into()));
+        self
+    }
+
+    /// Validate the input and create the [`PamRule`]
+    pub fn build(self) -> Result<PamRule, BuildError> {
+        let content = match self.inclusion {
+            Some(_) if self.control.is_some() || self.module.is_some() => {
+                return Err(BuildError::ConflictingContent)
+            }
+            Some(_) if !self.arguments.is_empty() => return Err(BuildError::ConflictingContent),
+            Some((method, service)) => {
+                validate_service(&service)?;
+                RuleContent::ServiceInclusion { method, service }
+            }
+            None => {
+                let control = self.control.ok_or(BuildError::MissingControl)?;
+                match &control {
+                    Control::Selection(selection) => validate_selection(selection.items())?,
+                    Control::Binding => return Err(BuildError::UnsupportedControl(control)),
+                    _ => {}
+                }
+
+                let module = self.module.ok_or(BuildError::MissingModule)?;
+                if module.contains(char::is_whitespace) {
+                    return Err(BuildError::InvalidModulePath(module));
+                }
+                let module_path = ModulePath::from_str(&module)
+                    .map_err(|_| BuildError::InvalidModulePath(module))?;
+
+                let module_arguments = self
+                    .arguments
+                    .into_iter()
+                    .map(parse_argument)
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                RuleContent::Entry {
+                    control,
+                    module_path,
+                    module_arguments,
+                }
+            }
+        };
+
+        Ok(PamRule {
+            span: Span::detached(),
+            is_logging_enabled: self.is_logging_enabled,
+            domain: Some(self.domain),
+            content,
+        })
+    }
+}
+
+impl Selection {
+    /// Create a builder for a selection such as `[success=ok default=bad]`
+    pub fn builder() -> SelectionBuilder {
+        SelectionBuilder::default()
+    }
+}
+
+impl SelectionBuilder {
+    /// Append a `value=action` item
+    pub fn item(mut self, value: impl Into<Value>, action: Action) -> Self {
+        self.items.push(SelectionItem::new(value.into(), action));
+        self
+    }
+
+    /// Validate the items and create the [`Selection`]
+    pub fn build(self) -> Result<Selection, BuildError> {
+        validate_selection(&self.items)?;
+        Ok(Selection::new(self.items))
+    }
+}
+
+fn validate_service(service: &str) -> Result<(), BuildError> {
+    // NOTE: Linux-PAM uses the service name as a file name within the configuration directory.
+    if !is_escapable(service) || service.contains(|c: char| c.is_whitespace() || c == '/') {
+        return Err(BuildError::InvalidService(service.to_string()));
+    }
+
+    Ok(())
+}
+
+fn parse_argument(argument: String) -> Result<ModuleArgument, BuildError> {
+    if !is_escapable(&argument) {
+        return Err(BuildError::InvalidArgument(argument));
+    }
+
+    ModuleArgument::from_str(&argument).map_err(|_| BuildError::InvalidArgument(argument))
+}
+
+fn validate_selection(items: &[SelectionItem]) -> Result<(), BuildError> {
+    if items.is_empty() {
+        return Err(BuildError::EmptySelection);
+    }
+
+    for (i, item) in items.iter().enumerate() {
+        if items[..i]
+            .iter()
+            .any(|earlier| earlier.value() == item.value())
+        {
+            return Err(BuildError::DuplicateValue(item.value()));
+        }
+
+        if item.action() == Action::JumpOver(0) {
+            return Err(BuildError::ZeroJump);
+        }
+    }
+
+    Ok(())
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use BuildError::*;
+
+        match self {
+            MissingControl => "rule has no control".fmt(f),
+            MissingModule => "rule has no module".fmt(f),
+            UnsupportedControl(control) => write!(f, "unsupported control `{}`", control),
+            ConflictingContent => "rule both includes a service and calls a module".fmt(f),
+            InvalidModulePath(path) => write!(f, "invalid module path `{}`", path),
+            InvalidArgument(argument) => write!(f, "invalid module argument `{}`", argument),
+            InvalidService(service) => write!(f, "invalid service name `{}`", service),
+            EmptySelection => "selection has no items".fmt(f),
+            DuplicateValue(value) => write!(f, "selection has more than one item for `{}`", value),
+            ZeroJump => "selection jumps over zero rules".fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PamService, PamWriter, ReturnCode};
+
+    #[test]
+    fn rule() {
+        let rule = PamRule::builder(Domain::Password)
+            .control(
+                Selection::builder()
+                    .item(ReturnCode::Success, Action::JumpOver(1))
+                    .item(Value::Default, Action::Ignore)
+                    .build()
+                    .unwrap(),
+            )
+            .module("pam_unix.so")
+            .args(["obscure", "sha512"])
+            .arg("foo=a b")
+            .logging(false)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            rule.to_string(),
+            "-password [success=1 default=ignore] pam_unix.so obscure sha512 [foo=a b]"
+        );
+
+        let include = PamRule::builder(Domain::Auth)
+            .include("common-auth")
+            .build()
+            .unwrap();
+        let directive = PamRule::include_directive("common-session").unwrap();
+
+        let written = PamWriter::new().write_rules(&[rule, include, directive]);
+        let parsed = PamService::from_str(&written).unwrap();
+        assert_eq!(PamWriter::new().write_rules(&parsed), written);
+    }
+
+    #[test]
+    fn errors() {
+        let builder = || PamRule::builder(Domain::Auth);
+
+        assert_eq!(
+            builder().module("pam_unix.so").build().unwrap_err(),
+            BuildError::MissingControl
+        );
+        assert_eq!(
+            builder().control(Control::Required).build().unwrap_err(),
+            BuildError::MissingModule
+        );
+        assert_eq!(
+            builder()
+                .control(Control::Binding)
+                .module("pam_unix.so")
+                .build()
+                .unwrap_err(),
+            BuildError::UnsupportedControl(Control::Binding)
+        );
+        assert_eq!(
+            builder()
+                .control(Control::Required)
+                .include("common-auth")
+                .build()
+                .unwrap_err(),
+            BuildError::ConflictingContent
+        );
+        assert_eq!(
+            builder()
+                .control(Control::Required)
+                .module("pam unix.so")
+                .build()
+                .unwrap_err(),
+            BuildError::InvalidModulePath("pam unix.so".to_string())
+        );
+        assert_eq!(
+            builder()
+                .control(Control::Required)
+                .module("pam_unix.so")
+                .arg("nullok\nauth")
+                .build()
+                .unwrap_err(),
+            BuildError::InvalidArgument("nullok\nauth".to_string())
+        );
+        assert_eq!(
+            builder()
+                .control(Control::Required)
+                .module("pam_unix.so")
+                .arg("")
+                .build()
+                .unwrap_err(),
+            BuildError::InvalidArgument(String::new())
+        );
+        assert_eq!(
+            builder()
+                .control(Control::Required)
+                .module("pam_unix.so")
+                .arg("foo=a b\\")
+                .build()
+                .unwrap_err(),
+            BuildError::InvalidArgument("foo=a b\\".to_string())
+        );
+        assert_eq!(
+            builder().substack("../shadow").build().unwrap_err(),
+            BuildError::InvalidService("../shadow".to_string())
+        );
+    }
+
+    #[test]
+    fn selection() {
+        assert_eq!(
+            Selection::builder().build().unwrap_err(),
+            BuildError::EmptySelection
+        );
+        assert_eq!(
+            Selection::builder()
+                .item(ReturnCode::Success, Action::Ok)
+                .item(ReturnCode::Success, Action::Bad)
+                .build()
+                .unwrap_err(),
+            BuildError::DuplicateValue(Value::ReturnCode(ReturnCode::Success))
+        );
+        assert_eq!(
+            Selection::builder()
+                .item(Value::Default, Action::JumpOver(0))
+                .build()
+                .unwrap_err(),
+            BuildError::ZeroJump
+        );
+    }
+}
@@ -3,18 +3,19 @@ use std::str::FromStr;
 
 use crate::return_code::ReturnCode;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Control {
     Required,
     Requisite,
     Sufficient,
     Optional,
-    Include,
-    Substack,
+    /// OpenPAM's `binding`, which ends the stack on success like `sufficient`, but fails the
+    /// stack on failure like `required`
+    Binding,
     Selection(Selection),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Selection(Vec<SelectionItem>);
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -54,6 +55,141 @@ pub enum ControlParseError {
     JumpOverflow,
 }
 
+impl Control {
+    /// Expand this control into a selection with an item for every [`ReturnCode`]
+    ///
+    /// The presets are short for the selections that Linux-PAM defines for them, e.g. `required`
+    /// is `[success=ok new_authtok_reqd=ok ignore=ignore default=bad]`. In the same way as
+    /// Linux-PAM, a later item for the same return code wins, `default` only applies to the return
+    /// codes that have no action yet, and the remaining return codes get `bad`. The items are in
+    /// the order of the numeric values of the return codes.
+    pub fn to_selection(&self) -> Selection {
+        use ReturnCode::{Ignore, NewAuthTokenRequired, Success};
+
+        let preset = |items: &[(ReturnCode, Action)], default| {
+            let mut items: Vec<SelectionItem> = items
+                .iter()
+                .map(|&(return_code, action)| SelectionItem::new(return_code.into(), action))
+                .collect();
+            items.push(SelectionItem::new(Value::Default, default));
+            items
+        };
+
+        let items = match self {
+            Control::Required => preset(
+                &[
+                    (Success, Action::Ok),
+                    (NewAuthTokenRequired, Action::Ok),
+                    (Ignore, Action::Ignore),
+                ],
+                Action::Bad,
+            ),
+            Control::Requisite => preset(
+                &[
+                    (Success, Action::Ok),
+                    (NewAuthTokenRequired, Action::Ok),
+                    (Ignore, Action::Ignore),
+                ],
+                Action::Die,
+            ),
+            Control::Sufficient => preset(
+                &[
+                    (Success, Action::Done),
+                    (NewAuthTokenRequired, Action::Done),
+                ],
+                Action::Ignore,
+            ),
+            Control::Optional => preset(
+                &[(Success, Action::Ok), (NewAuthTokenRequired, Action::Ok)],
+                Action::Ignore,
+            ),
+            Control::Binding => preset(
+                &[
+                    (Success, Action::Done),
+                    (NewAuthTokenRequired, Action::Done),
+                    (Ignore, Action::Ignore),
+                ],
+                Action::Bad,
+            ),
+            Control::Selection(selection) => selection.0.clone(),
+        };
+
+        let mut actions = [None; ReturnCode::ALL.len()];
+        for item in items {
+            match item.value {
+                Value::ReturnCode(return_code) => {
+                    let index = ReturnCode::ALL
+                        .iter()
+                        .position(|other| *other == return_code)
+                        .unwrap();
+                    actions[index] = Some(item.action);
+                }
+                Value::Default => actions
+                    .iter_mut()
+                    .filter(|action| action.is_none())
+                    .for_each(|action| *action = Some(item.action)),
+            }
+        }
+
+        Selection(
+            ReturnCode::ALL
+                .into_iter()
+                .zip(actions)
+                .map(|(return_code, action)| {
+                    SelectionItem::new(return_code.into(), action.unwrap_or(Action::Bad))
+                })
+                .collect(),
+        )
+    }
+
+    /// Get whether two controls take the same action for every [`ReturnCode`]
+    ///
+    /// This compares the [`Control::to_selection`] of both controls, so `required` is equivalent
+    /// to `[success=ok new_authtok_reqd=ok ignore=ignore default=bad]`.
+    pub fn is_equivalent(&self, other: &Control) -> bool {
+        self.to_selection() == other.to_selection()
+    }
+}
+
+impl Selection {
+    pub(crate) fn new(items: Vec<SelectionItem>) -> Self {
+        Self(items)
+    }
+
+    /// Get the `value=action` items in the order they are written
+    pub fn items(&self) -> &[SelectionItem] {
+        &self.0
+    }
+}
+
+impl SelectionItem {
+    pub(crate) fn new(value: Value, action: Action) -> Self {
+        Self { value, action }
+    }
+
+    /// Get the return value this item applies to
+    pub fn value(&self) -> Value {
+        self.value
+    }
+
+    /// Get the action that is taken for the return value
+    pub fn action(&self) -> Action {
+        self.action
+    }
+}
+
+impl From<Selection> for Control {
+    fn from(value: Selection) -> Self {
+        Control::Selection(value)
+    }
+}
+
+impl From<ReturnCode> for Value {
+    fn from(value: ReturnCode) -> Self {
+        Value::ReturnCode(value)
+    }
+}
+
 impl FromStr for Control {
     type Err = ControlParseError;
 
@@ -63,8 +199,7 @@ impl FromStr for Control {
             "requisite" => Control::Requisite,
             "sufficient" => Control::Sufficient,
             "optional" => Control::Optional,
-            "include" => Control::Include,
-            "substack" => Control::Substack,
+            "binding" => Control::Binding,
             s => Control::Selection(Selection::from_str(s)?),
         })
     }
@@ -82,9 +217,9 @@ impl FromStr for Selection {
             return Err(ControlParseError::UnclosedSelection);
         }
 
-        // TODO: This needs to be general whitespace
         let items = s[1..s.len() - 1]
-            .split(' ')
+            .split([' ', '\t'])
+            .filter(|item_str| !item_str.is_empty())
             .map(|item_str| SelectionItem::from_str(item_str))
             .collect::<Result<Vec<SelectionItem>, ControlParseError>>()?;
 
@@ -249,13 +384,33 @@ impl Display for Control {
             Control::Requisite => f.write_str("requisite"),
             Control::Sufficient => f.write_str("sufficient"),
             Control::Optional => f.write_str("optional"),
-            Control::Include => f.write_str("include"),
-            Control::Substack => f.write_str("substack"),
+            Control::Binding => f.write_str("binding"),
             Control::Selection(selection) => selection.fmt(f),
         }
     }
 }
 
+impl Display for ControlParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ControlParseError::*;
+
+        match self {
+            UnknownPreset(preset) => write!(f, "unknown control `{}`", preset),
+            UnknownValue(value) => write!(f, "unknown return value `{}`", value),
+            UnknownAction(action) => write!(f, "unknown action `{}`", action),
+            ExpectedEquals => f.write_str("expected `=` in selection"),
+            UnexpectedEnd => f.write_str("unexpected end of control"),
+            UnclosedSelection => f.write_str("unclosed selection"),
+            EmptyString => f.write_str("empty action"),
+            ZeroJump => f.write_str("jump of zero rules, use `ignore` instead"),
+            ExpectedDigit => f.write_str("expected a digit in jump"),
+            JumpOverflow => f.write_str("jump is too large"),
+        }
+    }
+}
+
+impl std::error::Error for ControlParseError {}
+
 impl Display for Selection {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str("[")?;
@@ -310,6 +465,53 @@ mod tests {
     #[test]
     fn control() {}
 
+    #[test]
+    fn to_selection() {
+        let required = Control::Required.to_selection();
+        assert_eq!(required.items().len(), ReturnCode::ALL.len());
+        assert_eq!(
+            required.items()[..3],
+            [
+                SelectionItem::new(ReturnCode::Success.into(), Action::Ok),
+                SelectionItem::new(ReturnCode::OpenError.into(), Action::Bad),
+                SelectionItem::new(ReturnCode::SymbolError.into(), Action::Bad),
+            ]
+        );
+
+        let equivalent =
+            Control::from_str("[default=bad ignore=ignore success=ok new_authtok_reqd=ok]")
+                .unwrap();
+        assert!(Control::Required.is_equivalent(&equivalent));
+        assert!(!Control::Requisite.is_equivalent(&equivalent));
+
+        let sufficient =
+            Control::from_str("[success=done new_authtok_reqd=done default=ignore]").unwrap();
+        assert!(Control::Sufficient.is_equivalent(&sufficient));
+
+        let binding =
+            Control::from_str("[success=done new_authtok_reqd=done ignore=ignore default=bad]")
+                .unwrap();
+        assert!(Control::Binding.is_equivalent(&binding));
+
+        // `default` does not override earlier items, later items override everything
+        let selection =
+            Control::from_str("[success=1 default=ignore success=done auth_err=die]").unwrap();
+        let actions: Vec<Action> = selection
+            .to_selection()
+            .items()
+            .iter()
+            .map(SelectionItem::action)
+            .collect();
+        assert_eq!(actions[0], Action::Done);
+        assert_eq!(actions[1], Action::Ignore);
+        assert_eq!(actions[7], Action::Die);
+
+        let unlisted = Control::from_str("[success=ok]").unwrap();
+        assert!(unlisted.to_selection().items()[1..]
+            .iter()
+            .all(|item| item.action() == Action::Bad));
+    }
+
     #[test]
     fn selection() {
         // macro_rules! assert_test {
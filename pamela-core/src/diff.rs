@@ -0,0 +1,741 @@
+//! Semantic comparison of resolved stacks
+//!
+//! A textual diff of a service file does not show how the services that include it change. This
+//! module compares the [`ResolvedStack`]s of two configurations instead, so a change to
+//! `system-auth` shows up in the stack of every service that executes it.
+//!
+//! Rules are matched by what they do rather than by how they are written. Every [`Change`] has an
+//! [`Impact`] that tells apart changes that alter the behavior of a stack from changes that only
+//! alter its spelling, such as `required` written out as a selection. Whitespace and comments are
+//! not part of the parsed rules, so changes to them are never reported.
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use crate::{
+    Action, Control, Domain, PamConfig, PamRule, ResolveError, ResolvedStack, RuleContent,
+    RuleOrigin, Selection, StackEntry,
+};
+
+/// Whether a [`Change`] affects what a stack does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Impact {
+    /// The rules are written differently, but the stack behaves the same
+    Cosmetic,
+    /// The stack behaves differently
+    Behavioral,
+}
+
+/// Kind of difference between two stacks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A rule only exists in the new stack
+    Added,
+    /// A rule only exists in the old stack
+    Removed,
+    /// A rule is executed in a different order relative to the other rules
+    Moved,
+    /// The control of a rule is different
+    ///
+    /// This is cosmetic if both controls take the same action for every return value and their
+    /// jumps land on the same rule, e.g. `required` and
+    /// `[success=ok new_authtok_reqd=ok ignore=ignore default=bad]`.
+    Control,
+    /// The module arguments of a rule are different
+    ///
+    /// Both lists are empty if only the order of the arguments is different.
+    Arguments {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    /// A rule is written down in a different service
+    Service,
+    /// The `-` in front of the domain is added or removed, which only changes whether a missing
+    /// module is logged
+    Logging,
+}
+
+/// Rule on one side of a [`Change`]
+#[derive(Debug, Clone, Copy)]
+pub struct DiffRule<'a> {
+    origin: RuleOrigin<'a>,
+    rule: &'a PamRule,
+    position: usize,
+}
+
+/// Single difference between an old and a new stack
+#[derive(Debug, Clone)]
+pub struct Change<'a> {
+    kind: ChangeKind,
+    impact: Impact,
+    domain: Domain,
+    old: Option<DiffRule<'a>>,
+    new: Option<DiffRule<'a>>,
+}
+
+/// All differences between the stacks of one service, as found by [`PamConfig::diff_service`]
+#[derive(Debug)]
+pub struct ServiceDiff<'a> {
+    service: String,
+    changes: Vec<Change<'a>>,
+}
+
+impl<'a> DiffRule<'a> {
+    /// Get where the rule was written down
+    pub fn origin(&self) -> RuleOrigin<'a> {
+        self.origin
+    }
+
+    /// Get the rule
+    pub fn rule(&self) -> &'a PamRule {
+        self.rule
+    }
+
+    /// Get the index of the rule within its stack, or within its substack
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<'a> Change<'a> {
+    /// Get the kind of difference
+    pub fn kind(&self) -> &ChangeKind {
+        &self.kind
+    }
+
+    /// Get whether the difference affects what the stack does
+    pub fn impact(&self) -> Impact {
+        self.impact
+    }
+
+    /// Get whether the difference affects what the stack does
+    pub fn is_behavioral(&self) -> bool {
+        self.impact == Impact::Behavioral
+    }
+
+    /// Get the [`Domain`] of the stacks that differ
+    pub fn domain(&self) -> Domain {
+        self.domain
+    }
+
+    /// Get the rule in the old stack, which is `None` for [`ChangeKind::Added`]
+    pub fn before(&self) -> Option<DiffRule<'a>> {
+        self.old
+    }
+
+    /// Get the rule in the new stack, which is `None` for [`ChangeKind::Removed`]
+    pub fn after(&self) -> Option<DiffRule<'a>> {
+        self.new
+    }
+}
+
+impl<'a> ServiceDiff<'a> {
+    /// Get the name of the service
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// Get the differences of all [`Domain`]s in execution order
+    pub fn changes(&self) -> &[Change<'a>] {
+        &self.changes
+    }
+
+    /// Get whether any of the differences affects what the service does
+    pub fn is_behavioral(&self) -> bool {
+        self.changes.iter().any(Change::is_behavioral)
+    }
+}
+
+impl From<Impact> for &'static str {
+    fn from(value: Impact) -> Self {
+        match value {
+            Impact::Cosmetic => "cosmetic",
+            Impact::Behavioral => "behavioral",
+        }
+    }
+}
+
+impl Display for Impact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <&'static str>::from(*self).fmt(f)
+    }
+}
+
+impl Display for Change<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]: ", self.impact, self.domain)?;
+
+        let (old, new) = match (self.old, self.new) {
+            (None, Some(new)) => return write!(f, "added `{}`", new.rule),
+            (Some(old), None) => return write!(f, "removed `{}`", old.rule),
+            (Some(old), Some(new)) => (old, new),
+            (None, None) => unreachable!(),
+        };
+
+        let target = target(new.rule);
+        match &self.kind {
+            ChangeKind::Added | ChangeKind::Removed => unreachable!(),
+            ChangeKind::Moved => write!(
+                f,
+                "moved `{}` from position {} to {}",
+                new.rule, old.position, new.position
+            ),
+            ChangeKind::Control => match (old.rule.control(), new.rule.control()) {
+                (Some(old_control), Some(new_control))
+                    if old_control.to_string() != new_control.to_string() =>
+                {
+                    write!(
+                        f,
+                        "changed control of `{}` from `{}` to `{}`",
+                        target, old_control, new_control
+                    )
+                }
+                _ => write!(f, "jump of `{}` lands on a different rule", target),
+            },
+            ChangeKind::Arguments { .. } => write!(
+                f,
+                "changed arguments of `{}` from `{}` to `{}`",
+                target,
+                arguments(old.rule).join(" "),
+                arguments(new.rule).join(" ")
+            ),
+            ChangeKind::Service => write!(
+                f,
+                "moved `{}` from `{}` to `{}`",
+                target,
+                old.origin.service(),
+                new.origin.service()
+            ),
+            ChangeKind::Logging => match new.rule.is_logging_enabled() {
+                true => write!(f, "enabled logging of a missing `{}`", target),
+                false => write!(f, "disabled logging of a missing `{}`", target),
+            },
+        }
+    }
+}
+
+impl PamConfig {
+    /// Compare the resolved stacks of all services with those of a `new` configuration
+    ///
+    /// Services that exist in either configuration are compared by name, see
+    /// [`PamConfig::diff_service`]. Only services with at least one difference are returned,
+    /// sorted by name.
+    pub fn diff<'a>(&'a self, new: &'a PamConfig) -> Result<Vec<ServiceDiff<'a>>, ResolveError> {
+        let names: BTreeSet<&str> = self
+            .services
+            .iter()
+            .chain(&new.services)
+            .map(|service| service.name())
+            .collect();
+
+        let mut diffs = Vec::new();
+        for name in names {
+            let diff = self.diff_service(new, name)?;
+            if !diff.changes.is_empty() {
+                diffs.push(diff);
+            }
+        }
+
+        Ok(diffs)
+    }
+
+    /// Compare the resolved stacks of all [`Domain`]s of a service with those of a `new`
+    /// configuration
+    ///
+    /// A service that has no configuration on one side, not even through the `other` service,
+    /// is compared as an empty stack. See [`ResolvedStack::diff`] for how rules are compared.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use pamela::{ChangeKind, PamConfig};
+    ///
+    /// let old = PamConfig::from_str(
+    ///     "sshd auth include system-auth\n\
+    ///      system-auth auth required pam_unix.so\n",
+    /// )?;
+    /// let new = PamConfig::from_str(
+    ///     "sshd auth include system-auth\n\
+    ///      system-auth auth required pam_unix.so nullok\n",
+    /// )?;
+    ///
+    /// let diff = old.diff_service(&new, "sshd")?;
+    /// assert!(diff.is_behavioral());
+    /// assert!(matches!(diff.changes()[0].kind(), ChangeKind::Arguments { .. }));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn diff_service<'a>(
+        &'a self,
+        new: &'a PamConfig,
+        service: &str,
+    ) -> Result<ServiceDiff<'a>, ResolveError> {
+        let domains = [
+            Domain::Auth,
+            Domain::Account,
+            Domain::Password,
+            Domain::Session,
+        ];
+
+        let mut changes = Vec::new();
+        for domain in domains {
+            let old_stack = resolve_if_exists(self, service, domain)?;
+            let new_stack = resolve_if_exists(new, service, domain)?;
+
+            changes.extend(diff_entries(
+                domain,
+                old_stack.as_ref().map_or(&[], ResolvedStack::entries),
+                new_stack.as_ref().map_or(&[], ResolvedStack::entries),
+            ));
+        }
+
+        Ok(ServiceDiff {
+            service: service.to_string(),
+            changes,
+        })
+    }
+}
+
+impl<'a> ResolvedStack<'a> {
+    /// Compare this stack with a `new` stack
+    ///
+    /// Rules are matched by their module, arguments and the actions of their control. Rules that
+    /// call the same module but differ otherwise are reported as changed, all other rules as
+    /// added or removed. Substacks are matched by the service they refer to and compared on
+    /// their own, since their jumps cannot leave the substack.
+    pub fn diff(&self, new: &ResolvedStack<'a>) -> Vec<Change<'a>> {
+        diff_entries(self.domain(), self.entries(), new.entries())
+    }
+}
+
+fn resolve_if_exists<'a>(
+    config: &'a PamConfig,
+    service: &str,
+    domain: Domain,
+) -> Result<Option<ResolvedStack<'a>>, ResolveError> {
+    match config.service(service) {
+        Some(_) => config.resolve(service, domain).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// What a rule does, regardless of how it is written down
+#[derive(PartialEq)]
+struct Behavior {
+    target: String,
+    arguments: Vec<String>,
+    actions: Option<Selection>,
+}
+
+impl Behavior {
+    fn of(rule: &PamRule) -> Self {
+        Self {
+            target: target(rule),
+            arguments: arguments(rule),
+            actions: rule.control().map(Control::to_selection),
+        }
+    }
+
+    fn has_same_target(&self, other: &Behavior) -> bool {
+        self.target == other.target && self.actions.is_some() == other.actions.is_some()
+    }
+}
+
+type Matcher = fn(&Behavior, &Behavior) -> bool;
+
+fn diff_entries<'a>(
+    domain: Domain,
+    old: &[StackEntry<'a>],
+    new: &[StackEntry<'a>],
+) -> Vec<Change<'a>> {
+    let old_behaviors: Vec<Behavior> = old.iter().map(|entry| Behavior::of(entry.rule())).collect();
+    let new_behaviors: Vec<Behavior> = new.iter().map(|entry| Behavior::of(entry.rule())).collect();
+
+    let mut old_to_new: Vec<Option<usize>> = vec![None; old.len()];
+    let mut is_new_matched = vec![false; new.len()];
+    let mut pairs = Vec::new();
+
+    for (i, j) in common_subsequence(&old_behaviors, &new_behaviors) {
+        old_to_new[i] = Some(j);
+        is_new_matched[j] = true;
+        pairs.push((i, j, false));
+    }
+
+    // Rules that do the same but are not part of the common subsequence have moved, rules that
+    // call the same module have changed
+    let matchers: [(bool, Matcher); 2] = [
+        (true, |old, new| old == new),
+        (false, Behavior::has_same_target),
+    ];
+    for (is_moved, matches) in matchers {
+        for i in 0..old.len() {
+            if old_to_new[i].is_some() {
+                continue;
+            }
+
+            let found = (0..new.len())
+                .find(|&j| !is_new_matched[j] && matches(&old_behaviors[i], &new_behaviors[j]));
+            if let Some(j) = found {
+                old_to_new[i] = Some(j);
+                is_new_matched[j] = true;
+                pairs.push((i, j, is_moved));
+            }
+        }
+    }
+
+    // Every group of changes is sorted by the position in the new stack, removed rules go
+    // right after the previous rule that still exists
+    let mut groups: Vec<((usize, bool), Vec<Change<'a>>)> = Vec::new();
+    let side = |entries: &[StackEntry<'a>], position: usize| DiffRule {
+        origin: entries[position].origin(),
+        rule: entries[position].rule(),
+        position,
+    };
+
+    for (i, j, is_moved) in pairs {
+        let (old_rule, new_rule) = (side(old, i), side(new, j));
+        let mut changes = Vec::new();
+        let mut change = |kind, impact| {
+            changes.push(Change {
+                kind,
+                impact,
+                domain,
+                old: Some(old_rule),
+                new: Some(new_rule),
+            })
+        };
+
+        if is_moved {
+            change(ChangeKind::Moved, Impact::Behavioral);
+        }
+
+        if let (Some(old_actions), Some(new_actions)) =
+            (&old_behaviors[i].actions, &new_behaviors[j].actions)
+        {
+            let lands_the_same = |old_jump: u32, new_jump: u32| {
+                let old_target = i + old_jump as usize + 1;
+                let new_target = j + new_jump as usize + 1;
+
+                match old_target >= old.len() {
+                    true => new_target >= new.len(),
+                    false => old_to_new[old_target] == Some(new_target),
+                }
+            };
+            let is_same =
+                old_actions
+                    .items()
+                    .iter()
+                    .zip(new_actions.items())
+                    .all(
+                        |(old_item, new_item)| match (old_item.action(), new_item.action()) {
+                            (Action::JumpOver(n), Action::JumpOver(m)) => lands_the_same(n, m),
+                            (old_action, new_action) => old_action == new_action,
+                        },
+                    );
+
+            if !is_same {
+                change(ChangeKind::Control, Impact::Behavioral);
+            } else if old_rule.rule.control().map(Control::to_string)
+                != new_rule.rule.control().map(Control::to_string)
+            {
+                change(ChangeKind::Control, Impact::Cosmetic);
+            }
+        }
+
+        let (old_arguments, new_arguments) =
+            (&old_behaviors[i].arguments, &new_behaviors[j].arguments);
+        if old_arguments != new_arguments {
+            change(
+                ChangeKind::Arguments {
+                    added: difference(new_arguments, old_arguments),
+                    removed: difference(old_arguments, new_arguments),
+                },
+                Impact::Behavioral,
+            );
+        }
+
+        if old_rule.origin.service() != new_rule.origin.service() {
+            change(ChangeKind::Service, Impact::Cosmetic);
+        }
+
+        if old_rule.rule.is_logging_enabled() != new_rule.rule.is_logging_enabled() {
+            change(ChangeKind::Logging, Impact::Cosmetic);
+        }
+
+        if let (
+            StackEntry::Substack {
+                stack: old_stack, ..
+            },
+            StackEntry::Substack {
+                stack: new_stack, ..
+            },
+        ) = (&old[i], &new[j])
+        {
+            changes.extend(old_stack.diff(new_stack));
+        }
+
+        groups.push(((j, true), changes));
+    }
+
+    for (i, _) in old_to_new.iter().enumerate().filter(|(_, j)| j.is_none()) {
+        let previous = old_to_new[..i].iter().rev().flatten().next();
+        groups.push((
+            (previous.map_or(0, |j| j + 1), false),
+            vec![Change {
+                kind: ChangeKind::Removed,
+                impact: Impact::Behavioral,
+                domain,
+                old: Some(side(old, i)),
+                new: None,
+            }],
+        ));
+    }
+
+    for j in (0..new.len()).filter(|&j| !is_new_matched[j]) {
+        groups.push((
+            (j, true),
+            vec![Change {
+                kind: ChangeKind::Added,
+                impact: Impact::Behavioral,
+                domain,
+                old: None,
+                new: Some(side(new, j)),
+            }],
+        ));
+    }
+
+    groups.sort_by_key(|(key, _)| *key);
+    groups
+        .into_iter()
+        .flat_map(|(_, changes)| changes)
+        .collect()
+}
+
+/// Find the longest common subsequence of equal behaviors as pairs of indices
+fn common_subsequence(old: &[Behavior], new: &[Behavior]) -> Vec<(usize, usize)> {
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = match old[i] == new[j] {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    pairs
+}
+
+/// Get the module or the service of a `substack` that a rule refers to
+fn target(rule: &PamRule) -> String {
+    match rule.content() {
+        RuleContent::Entry { module_path, .. } => module_path.to_string(),
+        RuleContent::ServiceInclusion { service, .. } => service.clone(),
+    }
+}
+
+fn arguments(rule: &PamRule) -> Vec<String> {
+    rule.module_arguments()
+        .iter()
+        .map(|argument| argument.to_string())
+        .collect()
+}
+
+/// Get the items of `a` that are not in `b`, counting duplicates
+fn difference(a: &[String], b: &[String]) -> Vec<String> {
+    let mut b: Vec<&String> = b.iter().collect();
+
+    a.iter()
+        .filter(|item| match b.iter().position(|other| other == item) {
+            Some(index) => {
+                b.remove(index);
+                false
+            }
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn changes(old: &str, new: &str) -> Vec<(ChangeKind, Impact)> {
+        let old = PamConfig::from_str(old).unwrap();
+        let new = PamConfig::from_str(new).unwrap();
+
+        old.diff_service(&new, "login")
+            .unwrap()
+            .changes()
+            .iter()
+            .map(|change| (change.kind().clone(), change.impact()))
+            .collect()
+    }
+
+    #[test]
+    fn behavioral() {
+        let old = r#"
+login   auth        required    pam_env.so
+login   auth        required    pam_unix.so nullok
+login   auth        requisite   pam_deny.so
+login   account     required    pam_nologin.so
+"#;
+        let new = r#"
+login   auth        required    pam_faillock.so preauth
+login   auth        sufficient  pam_unix.so try_first_pass
+login   auth        requisite   pam_deny.so
+login   account     required    pam_nologin.so
+"#;
+        assert_eq!(
+            changes(old, new),
+            [
+                (ChangeKind::Removed, Impact::Behavioral),
+                (ChangeKind::Added, Impact::Behavioral),
+                (ChangeKind::Control, Impact::Behavioral),
+                (
+                    ChangeKind::Arguments {
+                        added: vec!["try_first_pass".to_string()],
+                        removed: vec!["nullok".to_string()],
+                    },
+                    Impact::Behavioral
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn moved() {
+        let old = r#"
+login   session     required    pam_limits.so
+login   session     required    pam_unix.so
+login   session     optional    pam_motd.so
+"#;
+        let new = r#"
+login   session     optional    pam_motd.so
+login   session     required    pam_limits.so
+login   session     required    pam_unix.so
+"#;
+        assert_eq!(changes(old, new), [(ChangeKind::Moved, Impact::Behavioral)]);
+    }
+
+    #[test]
+    fn cosmetic() {
+        let old = r#"
+login   auth    required    pam_env.so
+login   auth    include     common-auth
+common-auth auth    required    pam_unix.so
+"#;
+        let new = r#"
+login   auth    [success=ok new_authtok_reqd=ok ignore=ignore default=bad]  pam_env.so
+login   auth    required    pam_unix.so
+login   -auth   required    pam_permit.so
+common-auth auth    required    pam_unix.so
+"#;
+        assert_eq!(
+            changes(old, new),
+            [
+                (ChangeKind::Control, Impact::Cosmetic),
+                (ChangeKind::Service, Impact::Cosmetic),
+                (ChangeKind::Added, Impact::Behavioral),
+            ]
+        );
+
+        let whitespace = r#"
+login auth required pam_env.so
+login	auth	include	common-auth
+# comment
+common-auth	auth	required	pam_unix.so
+"#;
+        assert_eq!(changes(old, whitespace), []);
+    }
+
+    #[test]
+    fn jumps() {
+        let old = r#"
+login   auth    [success=1 default=ignore]  pam_unix.so
+login   auth    requisite                   pam_deny.so
+login   auth    required                    pam_permit.so
+"#;
+        let renumbered = r#"
+login   auth    [success=2 default=ignore]  pam_unix.so
+login   auth    requisite                   pam_deny.so
+login   auth    optional                    pam_echo.so
+login   auth    required                    pam_permit.so
+"#;
+        let shifted = r#"
+login   auth    [success=1 default=ignore]  pam_unix.so
+login   auth    requisite                   pam_deny.so
+login   auth    optional                    pam_echo.so
+login   auth    required                    pam_permit.so
+"#;
+        assert_eq!(
+            changes(old, renumbered),
+            [
+                (ChangeKind::Control, Impact::Cosmetic),
+                (ChangeKind::Added, Impact::Behavioral),
+            ]
+        );
+        assert_eq!(
+            changes(old, shifted),
+            [
+                (ChangeKind::Control, Impact::Behavioral),
+                (ChangeKind::Added, Impact::Behavioral),
+            ]
+        );
+    }
+
+    #[test]
+    fn config() {
+        let old = PamConfig::from_str(
+            r#"
+sshd        auth    include     system-auth
+login       auth    include     system-auth
+system-auth auth    required    pam_unix.so
+passwd      auth    required    pam_unix.so
+"#,
+        )
+        .unwrap();
+        let new = PamConfig::from_str(
+            r#"
+sshd        auth    include     system-auth
+login       auth    include     system-auth
+system-auth auth    required    pam_unix.so nullok
+passwd      auth    substack    system-auth
+"#,
+        )
+        .unwrap();
+
+        let diffs = old.diff(&new).unwrap();
+        let services: Vec<&str> = diffs.iter().map(ServiceDiff::service).collect();
+        assert_eq!(services, ["login", "passwd", "sshd", "system-auth"]);
+
+        let passwd: Vec<&ChangeKind> = diffs[1].changes().iter().map(Change::kind).collect();
+        assert_eq!(passwd, [&ChangeKind::Removed, &ChangeKind::Added]);
+
+        assert_eq!(
+            diffs[2].changes()[0].to_string(),
+            "behavioral[auth]: changed arguments of `pam_unix.so` from `` to `nullok`"
+        );
+        assert_eq!(
+            diffs[1].changes()[1].to_string(),
+            "behavioral[auth]: added `auth substack system-auth`"
+        );
+    }
+}
@@ -0,0 +1,848 @@
+//! Editing a service file while keeping its formatting
+//!
+//! The [`ServiceEditor`] works on a [`SyntaxTree`], so comments, blank lines and the alignment of
+//! rules that are not edited are written back untouched. New rules are aligned to the columns of
+//! a neighbouring rule.
+//!
+//! Inserting or removing a rule changes the number of rules that `value=N` jumps skip over. The
+//! editor renumbers every jump of the same domain that crosses the edit, in the same way as
+//! authselect and pam-auth-update do. The number of rules that an `include` or `@include` rule
+//! adds is not known from the file alone, so an edit that changes a jump across such a rule, or
+//! that inserts, removes or replaces such a rule within a jump, fails with
+//! [`EditError::JumpOverInclude`]. A `substack` counts as a single rule.
+
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::parsing::{is_escapable, take_string};
+use crate::writer::rule_columns;
+use crate::{
+    Action, Control, Domain, InclusionMethod, ModuleArgument, PamConfigSyntaxError, PamRule,
+    RuleNode, Selection, SelectionItem, SyntaxNode, SyntaxToken, SyntaxTree, TokenKind,
+};
+
+/// Number of columns between tab stops, used to align new rules to rules that use tabs
+const TAB_WIDTH: usize = 8;
+
+/// Domains that an `@include` directive adds rules to
+const DOMAINS: [Domain; 4] = [
+    Domain::Auth,
+    Domain::Account,
+    Domain::Password,
+    Domain::Session,
+];
+
+/// Editor for a service file in the separated configuration format
+///
+/// Rules are addressed by their index among all rules of the file, which does not count comments
+/// and blank lines.
+///
+/// # Examples
+///
+/// ```
+/// use pamela::{Control, Domain, PamRule, ServiceEditor};
+///
+/// let source = "# Authentication\nauth [success=1 default=ignore] pam_unix.so\nauth requisite pam_deny.so\n";
+/// let mut editor = ServiceEditor::parse(source);
+///
+/// let rule = PamRule::builder(Domain::Auth)
+///     .control(Control::Sufficient)
+///     .module("pam_sss.so")
+///     .build()?;
+/// let is_deny = |rule: &PamRule| rule.module_path().is_some_and(|path| path.to_string() == "pam_deny.so");
+/// editor.insert_before(is_deny, rule)?;
+///
+/// assert_eq!(
+///     editor.to_string(),
+///     "# Authentication\nauth [success=2 default=ignore] pam_unix.so\nauth sufficient pam_sss.so\nauth requisite pam_deny.so\n"
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServiceEditor {
+    tree: SyntaxTree,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    /// No rule matches the predicate
+    NoMatch,
+    /// There is no rule with this index
+    OutOfBounds(usize),
+    /// The rule with this index includes another service, so it has no module arguments
+    NotAModule(usize),
+    /// The module argument cannot be written in a service file
+    InvalidArgument(String),
+    /// Removing the rule leaves the jump of the rule with this index without rules to jump over
+    EmptyJump(usize),
+    /// The jump of the rule with this index crosses an included service, so it cannot be
+    /// renumbered
+    JumpOverInclude(usize),
+    /// Inserting the rule makes the jump of the rule with this index too long
+    JumpOverflow(usize),
+}
+
+/// Alignment of the columns of an existing rule
+#[derive(Debug, Default)]
+struct Layout {
+    /// Visual start of every column
+    starts: Vec<usize>,
+    /// Whitespace in front of every column
+    gaps: Vec<String>,
+}
+
+impl ServiceEditor {
+    /// Parse a service file for editing
+    pub fn parse(source: &str) -> Self {
+        Self {
+            tree: SyntaxTree::parse(source),
+        }
+    }
+
+    /// Read a service file for editing
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        fs::read_to_string(path).map(|source| Self::parse(&source))
+    }
+
+    /// Write the edited service file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Get the edited [`SyntaxTree`]
+    pub fn tree(&self) -> &SyntaxTree {
+        &self.tree
+    }
+
+    /// Get the number of rules
+    pub fn len(&self) -> usize {
+        self.tree.rule_nodes().count()
+    }
+
+    /// Get whether there are no rules
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the typed rule with an index
+    pub fn rule(&self, index: usize) -> Option<Result<PamRule, PamConfigSyntaxError>> {
+        self.tree.rule_nodes().nth(index).map(RuleNode::rule)
+    }
+
+    /// Find the index of the first rule that matches a predicate
+    ///
+    /// Rules with a syntax error never match.
+    pub fn position(&self, mut predicate: impl FnMut(&PamRule) -> bool) -> Option<usize> {
+        self.tree
+            .rule_nodes()
+            .position(|node| node.rule().is_ok_and(|rule| predicate(&rule)))
+    }
+
+    /// Insert a rule so that it gets `index`, or append it if `index` is the number of rules
+    pub fn insert(&mut self, index: usize, rule: PamRule) -> Result<(), EditError> {
+        let position = match self.node_position(index) {
+            Some(position) => position,
+            None if index == self.len() => self.tree.nodes().len(),
+            None => return Err(EditError::OutOfBounds(index)),
+        };
+        let template = self.node_position(index).or_else(|| {
+            let last = index.checked_sub(1)?;
+            self.node_position(last)
+        });
+
+        self.insert_node(position, template, rule)
+    }
+
+    /// Insert a rule directly before the first rule that matches a predicate
+    ///
+    /// Returns the index of the inserted rule.
+    pub fn insert_before(
+        &mut self,
+        predicate: impl FnMut(&PamRule) -> bool,
+        rule: PamRule,
+    ) -> Result<usize, EditError> {
+        let index = self.position(predicate).ok_or(EditError::NoMatch)?;
+        let position = self.node_position(index).expect("The rule was just found");
+
+        self.insert_node(position, Some(position), rule)?;
+        Ok(index)
+    }
+
+    /// Insert a rule directly after the first rule that matches a predicate
+    ///
+    /// Returns the index of the inserted rule.
+    pub fn insert_after(
+        &mut self,
+        predicate: impl FnMut(&PamRule) -> bool,
+        rule: PamRule,
+    ) -> Result<usize, EditError> {
+        let index = self.position(predicate).ok_or(EditError::NoMatch)?;
+        let position = self.node_position(index).expect("The rule was just found");
+
+        self.insert_node(position + 1, Some(position), rule)?;
+        Ok(index + 1)
+    }
+
+    /// Remove the rule with an index
+    ///
+    /// Comments in front of the rule are kept.
+    pub fn remove(&mut self, index: usize) -> Result<(), EditError> {
+        let position = self
+            .node_position(index)
+            .ok_or(EditError::OutOfBounds(index))?;
+
+        let node = self.rule_node(position);
+        let is_include = node_is_include(node);
+        for domain in node_jump_domains(node) {
+            self.renumber(domain, position, false, is_include)?;
+        }
+
+        self.tree.nodes_mut().remove(position);
+        Ok(())
+    }
+
+    /// Replace the rule with an index, keeping its alignment
+    pub fn replace(&mut self, index: usize, rule: PamRule) -> Result<(), EditError> {
+        let position = self
+            .node_position(index)
+            .ok_or(EditError::OutOfBounds(index))?;
+
+        let node = self.rule_node(position);
+        if node_domain(node) == rule.domain() {
+            let replacement = format_rule(&rule, Some(node));
+            // Replacing an include changes the number of rules that a jump across it skips
+            if node_is_include(node) || node_is_include(&replacement) {
+                for domain in node_jump_domains(&replacement) {
+                    self.renumber(domain, position, false, true)?;
+                }
+            }
+            self.tree.nodes_mut()[position] = SyntaxNode::Rule(replacement);
+            return Ok(());
+        }
+
+        // The rule moves to another domain, so the jumps of both domains change
+        let template = node.clone();
+        let original = self.tree.clone();
+        let replacement = format_rule(&rule, Some(&template));
+        let is_include = node_is_include(&replacement);
+        let renumbered = self.remove(index).and_then(|()| {
+            node_jump_domains(&replacement)
+                .into_iter()
+                .try_for_each(|domain| self.renumber(domain, position, true, is_include))
+        });
+        if let Err(err) = renumbered {
+            self.tree = original;
+            return Err(err);
+        }
+        self.tree
+            .nodes_mut()
+            .insert(position, SyntaxNode::Rule(replacement));
+
+        Ok(())
+    }
+
+    /// Append a module argument, e.g. `nullok` or `deny=3`, to the rule with an index
+    pub fn add_argument(&mut self, index: usize, argument: &str) -> Result<(), EditError> {
+        let position = self
+            .node_position(index)
+            .ok_or(EditError::OutOfBounds(index))?;
+
+        if self.rule_node(position).control().is_none() {
+            return Err(EditError::NotAModule(index));
+        }
+
+        if !is_escapable(argument) {
+            return Err(EditError::InvalidArgument(argument.to_string()));
+        }
+        let argument = ModuleArgument::from_str(argument)
+            .map_err(|_| EditError::InvalidArgument(argument.to_string()))?;
+
+        let tokens = self.rule_node_mut(position).tokens_mut();
+        let end = tokens
+            .iter()
+            .rposition(|token| !matches!(token.kind(), TokenKind::Whitespace | TokenKind::LineFeed))
+            .map_or(0, |last| last + 1);
+
+        tokens.splice(
+            end..end,
+            [
+                SyntaxToken::new(TokenKind::Whitespace, " ".to_string()),
+                SyntaxToken::new(TokenKind::ModuleArgument, argument.to_string()),
+            ],
+        );
+
+        Ok(())
+    }
+
+    /// Remove all module arguments with a name from the rule with an index
+    ///
+    /// The name is the part before the `=`. Returns the number of removed arguments.
+    pub fn remove_argument(&mut self, index: usize, name: &str) -> Result<usize, EditError> {
+        let position = self
+            .node_position(index)
+            .ok_or(EditError::OutOfBounds(index))?;
+
+        if self.rule_node(position).control().is_none() {
+            return Err(EditError::NotAModule(index));
+        }
+
+        let tokens = self.rule_node_mut(position).tokens_mut();
+        let mut removed = 0;
+        let mut i = 0;
+        while i < tokens.len() {
+            if tokens[i].kind() != TokenKind::ModuleArgument || argument_name(&tokens[i]) != name {
+                i += 1;
+                continue;
+            }
+
+            // Keep the whitespace in front of the argument if another argument follows
+            let followed =
+                tokens.get(i + 2).map(SyntaxToken::kind) == Some(TokenKind::ModuleArgument);
+            if followed && tokens[i + 1].kind() == TokenKind::Whitespace {
+                tokens.drain(i..i + 2);
+            } else if i > 0 && tokens[i - 1].kind() == TokenKind::Whitespace {
+                tokens.drain(i - 1..=i);
+                i -= 1;
+            } else {
+                tokens.remove(i);
+            }
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// Get the position in the nodes of the tree of the rule with an index
+    fn node_position(&self, index: usize) -> Option<usize> {
+        self.tree
+            .nodes()
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, SyntaxNode::Rule(_)))
+            .nth(index)
+            .map(|(position, _)| position)
+    }
+
+    fn rule_node(&self, position: usize) -> &RuleNode {
+        match &self.tree.nodes()[position] {
+            SyntaxNode::Rule(node) => node,
+            _ => unreachable!("Node positions always point at rules"),
+        }
+    }
+
+    fn rule_node_mut(&mut self, position: usize) -> &mut RuleNode {
+        match &mut self.tree.nodes_mut()[position] {
+            SyntaxNode::Rule(node) => node,
+            _ => unreachable!("Node positions always point at rules"),
+        }
+    }
+
+    /// Insert a rule at a node position, aligned to the rule at the `template` position
+    fn insert_node(
+        &mut self,
+        position: usize,
+        template: Option<usize>,
+        rule: PamRule,
+    ) -> Result<(), EditError> {
+        let template = template.map(|template| self.rule_node(template));
+        let node = format_rule(&rule, template);
+
+        let is_include = node_is_include(&node);
+        for domain in node_jump_domains(&node) {
+            self.renumber(domain, position, true, is_include)?;
+        }
+
+        let nodes = self.tree.nodes_mut();
+        if position == nodes.len() {
+            terminate_line(nodes.last_mut());
+        }
+        nodes.insert(position, SyntaxNode::Rule(node));
+        Ok(())
+    }
+
+    /// Adjust the jumps in `domain` that cross a rule that is inserted or removed at a node
+    /// position
+    ///
+    /// If the rule is an include, every jump that crosses it is an error. Nothing is changed if
+    /// any of the jumps cannot be adjusted.
+    fn renumber(
+        &mut self,
+        domain: Domain,
+        position: usize,
+        inserted: bool,
+        is_include: bool,
+    ) -> Result<(), EditError> {
+        let mut changes = Vec::new();
+
+        let rules =
+            self.tree.nodes()[..position]
+                .iter()
+                .enumerate()
+                .filter_map(|(position, node)| match node {
+                    SyntaxNode::Rule(node) => Some((position, node)),
+                    _ => None,
+                });
+
+        // Rules of `domain` before the edit, counted backwards from the edit
+        let mut distance = 0;
+        let mut is_include_between = is_include;
+        for (index, (rule_position, node)) in
+            rules.enumerate().collect::<Vec<_>>().into_iter().rev()
+        {
+            let is_inclusion = node_is_include(node);
+            match node_domain(node) {
+                // An `@include` directive adds rules to every domain
+                None if is_inclusion => {
+                    is_include_between = true;
+                    continue;
+                }
+                Some(node_domain) if node_domain == domain => {}
+                _ => continue,
+            }
+
+            if let Some(Ok(Control::Selection(selection))) = node.control() {
+                let mut changed = false;
+                let items = selection
+                    .items()
+                    .iter()
+                    .map(|item| match item.action() {
+                        // The included rules count as well, so the end of the jump is not known
+                        Action::JumpOver(n) if n as usize > distance && is_include_between => {
+                            Err(EditError::JumpOverInclude(index))
+                        }
+                        Action::JumpOver(n) if n as usize > distance => {
+                            changed = true;
+                            let n = match inserted {
+                                true => n.checked_add(1).ok_or(EditError::JumpOverflow(index))?,
+                                false => n - 1,
+                            };
+                            Ok(SelectionItem::new(item.value(), Action::JumpOver(n)))
+                        }
+                        _ => Ok(item.clone()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if items
+                    .iter()
+                    .any(|item| item.action() == Action::JumpOver(0))
+                {
+                    return Err(EditError::EmptyJump(index));
+                }
+
+                if changed {
+                    changes.push((rule_position, Selection::new(items)));
+                }
+            }
+
+            is_include_between |= is_inclusion;
+            distance += 1;
+        }
+
+        for (rule_position, selection) in changes {
+            let control = Control::Selection(selection).to_string();
+            let tokens = self.rule_node_mut(rule_position).tokens_mut();
+            if let Some(token) = tokens
+                .iter_mut()
+                .find(|token| token.kind() == TokenKind::Control)
+            {
+                token.set_text(control);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for ServiceEditor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.tree.fmt(f)
+    }
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use EditError::*;
+
+        match self {
+            NoMatch => "no rule matches".fmt(f),
+            OutOfBounds(index) => write!(f, "there is no rule {}", index),
+            NotAModule(index) => write!(f, "rule {} does not call a module", index),
+            InvalidArgument(argument) => write!(f, "invalid module argument `{}`", argument),
+            EmptyJump(index) => write!(f, "the jump of rule {} would jump over no rules", index),
+            JumpOverInclude(index) => {
+                write!(f, "the jump of rule {} crosses an included service", index)
+            }
+            JumpOverflow(index) => write!(f, "the jump of rule {} would be too long", index),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+/// Get the domain of a rule node, which is `None` for an `@include` directive
+fn node_domain(node: &RuleNode) -> Option<Domain> {
+    match node.domain() {
+        Some(Ok((domain, _))) => Some(domain),
+        _ => None,
+    }
+}
+
+/// Get the domains in which a rule node counts for jumps, which are all of them for an
+/// `@include` directive
+fn node_jump_domains(node: &RuleNode) -> Vec<Domain> {
+    match node_domain(node) {
+        Some(domain) => vec![domain],
+        None if node.rule().is_ok_and(|rule| rule.is_include_directive()) => DOMAINS.to_vec(),
+        None => Vec::new(),
+    }
+}
+
+/// Get whether a rule node adds the rules of another service to the stack
+///
+/// A `substack` is not an include, because it runs as a single rule.
+fn node_is_include(node: &RuleNode) -> bool {
+    node.rule().is_ok_and(|rule| {
+        matches!(
+            rule.inclusion_method(),
+            Some(InclusionMethod::Include | InclusionMethod::IncludeDirective)
+        )
+    })
+}
+
+/// Get the name of a module argument token, i.e. the part before the `=`
+fn argument_name(token: &SyntaxToken) -> String {
+    let value = token.value();
+    let Some((value, _)) = take_string(&value) else {
+        return String::new();
+    };
+
+    match value.split_once('=') {
+        Some((name, _)) => name.to_string(),
+        None => value.into_owned(),
+    }
+}
+
+/// Make sure that a node ends with a line feed, so that a node can be added after it
+fn terminate_line(node: Option<&mut SyntaxNode>) {
+    match node {
+        Some(SyntaxNode::Rule(node))
+            if node.tokens().last().map(SyntaxToken::kind) != Some(TokenKind::LineFeed) =>
+        {
+            let token = SyntaxToken::new(TokenKind::LineFeed, "\n".to_string());
+            node.tokens_mut().push(token);
+        }
+        Some(SyntaxNode::Header(text) | SyntaxNode::Comment(text) | SyntaxNode::Blank(text))
+            if !text.ends_with('\n') =>
+        {
+            text.push('\n');
+        }
+        _ => {}
+    }
+}
+
+/// Write a rule as a node, aligned to the columns of `template`
+fn format_rule(rule: &PamRule, template: Option<&RuleNode>) -> RuleNode {
+    let layout = template.map(Layout::of).unwrap_or_default();
+
+    let mut line = String::new();
+    for (i, column) in rule_columns(rule).iter().enumerate() {
+        if i > 0 {
+            match (layout.gaps.get(i), layout.starts.get(i)) {
+                (Some(gap), _) if gap.contains('\t') => line.push_str(gap),
+                (_, Some(start)) => {
+                    let width = visual_width(&line);
+                    line.push_str(&" ".repeat(start.saturating_sub(width).max(1)));
+                }
+                _ => line.push(' '),
+            }
+        }
+
+        line.push_str(column);
+    }
+    line.push('\n');
+
+    RuleNode::lex(&line)
+}
+
+impl Layout {
+    fn of(node: &RuleNode) -> Self {
+        let mut layout = Layout::default();
+        let mut line = String::new();
+        let mut gap = String::new();
+
+        for token in node.tokens() {
+            match token.kind() {
+                // Alignment after a line continuation is not worth following
+                TokenKind::LineFeed | TokenKind::Directive => break,
+                TokenKind::Whitespace if token.text().contains('\n') => break,
+                TokenKind::Whitespace => gap.push_str(token.text()),
+                kind => {
+                    // All module arguments form a single column
+                    line.push_str(&gap);
+                    if kind != TokenKind::ModuleArgument || layout.starts.len() < 4 {
+                        layout.starts.push(visual_width(&line));
+                        layout.gaps.push(std::mem::take(&mut gap));
+                    }
+                    line.push_str(token.text());
+                }
+            }
+        }
+
+        layout
+    }
+}
+
+/// Width of a line when tabs are expanded
+fn visual_width(s: &str) -> usize {
+    s.chars().fold(0, |width, c| match c {
+        '\t' => (width / TAB_WIDTH + 1) * TAB_WIDTH,
+        _ => width + 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSTEM_AUTH: &str = r#"#%PAM-1.0
+
+auth       required                    pam_faillock.so      preauth
+# Try the home directory first
+-auth      [success=2 default=ignore]  pam_systemd_home.so
+auth       [success=1 default=bad]     pam_unix.so          try_first_pass nullok
+auth       [default=die]               pam_faillock.so      authfail
+auth       required                    pam_env.so
+
+account    required                    pam_unix.so
+"#;
+
+    fn module(name: &'static str) -> impl Fn(&PamRule) -> bool {
+        move |rule| {
+            rule.module_path()
+                .is_some_and(|path| path.to_string() == name)
+        }
+    }
+
+    fn rule(s: &str) -> PamRule {
+        PamRule::separated_iter(s).next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn insert() {
+        let mut editor = ServiceEditor::parse(SYSTEM_AUTH);
+
+        let index = editor
+            .insert_before(module("pam_unix.so"), rule("auth sufficient pam_sss.so"))
+            .unwrap();
+        assert_eq!(index, 2);
+        editor
+            .insert(editor.len(), rule("session optional pam_permit.so"))
+            .unwrap();
+
+        assert_eq!(
+            editor.to_string(),
+            r#"#%PAM-1.0
+
+auth       required                    pam_faillock.so      preauth
+# Try the home directory first
+-auth      [success=3 default=ignore]  pam_systemd_home.so
+auth       sufficient                  pam_sss.so
+auth       [success=1 default=bad]     pam_unix.so          try_first_pass nullok
+auth       [default=die]               pam_faillock.so      authfail
+auth       required                    pam_env.so
+
+account    required                    pam_unix.so
+session    optional                    pam_permit.so
+"#
+        );
+
+        // Inserting in front of the target of a jump does not change it
+        let mut editor = ServiceEditor::parse(SYSTEM_AUTH);
+        editor
+            .insert_after(
+                module("pam_faillock.so"),
+                rule("account required pam_time.so"),
+            )
+            .unwrap();
+        editor
+            .insert_before(module("pam_env.so"), rule("auth optional pam_permit.so"))
+            .unwrap();
+        assert!(editor.to_string().contains("[success=2 default=ignore]"));
+        assert!(editor.to_string().contains("[success=1 default=bad]"));
+
+        assert_eq!(
+            editor.insert_before(module("pam_missing.so"), rule("auth required pam_env.so")),
+            Err(EditError::NoMatch)
+        );
+        assert_eq!(
+            editor.insert(100, rule("auth required pam_env.so")),
+            Err(EditError::OutOfBounds(100))
+        );
+    }
+
+    #[test]
+    fn remove_and_replace() {
+        let mut editor = ServiceEditor::parse(SYSTEM_AUTH);
+
+        // The jump of `pam_unix.so` only skips the rule that is removed
+        assert_eq!(editor.remove(3), Err(EditError::EmptyJump(2)));
+        assert_eq!(editor.to_string(), SYSTEM_AUTH);
+
+        let index = editor.position(module("pam_env.so")).unwrap();
+        editor.remove(index).unwrap();
+        editor
+            .replace(0, rule("auth requisite pam_faillock.so preauth silent"))
+            .unwrap();
+
+        assert_eq!(
+            editor.to_string(),
+            r#"#%PAM-1.0
+
+auth       requisite                   pam_faillock.so      preauth silent
+# Try the home directory first
+-auth      [success=2 default=ignore]  pam_systemd_home.so
+auth       [success=1 default=bad]     pam_unix.so          try_first_pass nullok
+auth       [default=die]               pam_faillock.so      authfail
+
+account    required                    pam_unix.so
+"#
+        );
+
+        // Moving the rule after a jump to another domain shortens the jump
+        let index = editor.position(module("pam_systemd_home.so")).unwrap();
+        editor
+            .replace(index + 1, rule("account required pam_time.so"))
+            .unwrap();
+        assert!(editor.to_string().contains("[success=1 default=ignore]"));
+    }
+
+    #[test]
+    fn jumps_over_includes() {
+        let source = r#"auth       [success=2 default=ignore]  pam_unix.so
+auth       include                     common-auth
+@include common-password
+auth       requisite                   pam_deny.so
+auth       required                    pam_permit.so
+"#;
+        let mut editor = ServiceEditor::parse(source);
+
+        // The included rules are not known, so neither is the end of the jump
+        assert_eq!(
+            editor.insert_before(module("pam_deny.so"), rule("auth optional pam_env.so")),
+            Err(EditError::JumpOverInclude(0))
+        );
+        assert_eq!(editor.remove(3), Err(EditError::JumpOverInclude(0)));
+        assert_eq!(
+            editor.replace(2, rule("auth required pam_env.so")),
+            Err(EditError::JumpOverInclude(0))
+        );
+        assert_eq!(
+            editor.replace(3, rule("account required pam_time.so")),
+            Err(EditError::JumpOverInclude(0))
+        );
+        assert_eq!(editor.to_string(), source);
+
+        // An include after the edit does not matter
+        let mut editor = ServiceEditor::parse(
+            "auth [success=1 default=ignore] pam_unix.so\nauth include common-auth\n",
+        );
+        editor.insert(1, rule("auth optional pam_env.so")).unwrap();
+        assert!(editor.to_string().contains("[success=2 default=ignore]"));
+
+        // Neither can an include be inserted, removed or replaced within a jump
+        let source = "auth [success=1 default=ignore] pam_unix.so\nauth required pam_env.so\n";
+        let mut editor = ServiceEditor::parse(source);
+        assert_eq!(
+            editor.insert(1, rule("auth include common-auth")),
+            Err(EditError::JumpOverInclude(0))
+        );
+        assert_eq!(
+            editor.insert(1, rule("@include common-auth")),
+            Err(EditError::JumpOverInclude(0))
+        );
+        assert_eq!(
+            editor.replace(1, rule("auth include common-auth")),
+            Err(EditError::JumpOverInclude(0))
+        );
+        assert_eq!(editor.to_string(), source);
+
+        let mut editor = ServiceEditor::parse(
+            "auth [success=2 default=ignore] pam_unix.so\nauth include common-auth\nauth required pam_env.so\n",
+        );
+        assert_eq!(editor.remove(1), Err(EditError::JumpOverInclude(0)));
+        assert_eq!(
+            editor.replace(1, rule("auth required pam_permit.so")),
+            Err(EditError::JumpOverInclude(0))
+        );
+
+        // A substack is a single rule
+        let mut editor = ServiceEditor::parse(
+            "auth [success=2 default=ignore] pam_unix.so\nauth substack common-auth\nauth required pam_env.so\n",
+        );
+        editor
+            .insert_before(module("pam_env.so"), rule("auth optional pam_permit.so"))
+            .unwrap();
+        assert!(editor.to_string().contains("[success=3 default=ignore]"));
+        editor.remove(1).unwrap();
+        assert!(editor.to_string().contains("[success=2 default=ignore]"));
+        editor.insert(1, rule("auth substack common-auth")).unwrap();
+        assert!(editor.to_string().contains("[success=3 default=ignore]"));
+
+        let mut editor = ServiceEditor::parse(&format!(
+            "auth [success={} default=ignore] pam_unix.so\nauth required pam_env.so\n",
+            u32::MAX
+        ));
+        assert_eq!(
+            editor.insert(1, rule("auth optional pam_permit.so")),
+            Err(EditError::JumpOverflow(0))
+        );
+    }
+
+    #[test]
+    fn arguments() {
+        let mut editor =
+            ServiceEditor::parse("auth\trequired\tpam_unix.so\tnullok try_first_pass\n");
+
+        editor.add_argument(0, "remember=5").unwrap();
+        editor.add_argument(0, "foo=a b").unwrap();
+        assert_eq!(editor.remove_argument(0, "nullok").unwrap(), 1);
+        assert_eq!(editor.remove_argument(0, "foo").unwrap(), 1);
+        assert_eq!(editor.remove_argument(0, "nullok").unwrap(), 0);
+
+        assert_eq!(
+            editor.to_string(),
+            "auth\trequired\tpam_unix.so\ttry_first_pass remember=5\n"
+        );
+
+        // A trailing backslash would continue the line or escape the closing bracket
+        assert_eq!(
+            editor.add_argument(0, "foo=a\\"),
+            Err(EditError::InvalidArgument("foo=a\\".to_string()))
+        );
+
+        let mut editor = ServiceEditor::parse("auth include common-auth");
+        assert_eq!(
+            editor.add_argument(0, "nullok"),
+            Err(EditError::NotAModule(0))
+        );
+
+        // New rules follow tabs and files without a final line feed
+        editor.insert(1, rule("auth required pam_deny.so")).unwrap();
+        assert_eq!(
+            editor.to_string(),
+            "auth include common-auth\nauth required pam_deny.so\n"
+        );
+
+        let mut editor = ServiceEditor::parse("auth\trequired\tpam_env.so\n");
+        editor
+            .insert(0, rule("auth sufficient pam_rootok.so"))
+            .unwrap();
+        assert_eq!(
+            editor.to_string(),
+            "auth\tsufficient\tpam_rootok.so\nauth\trequired\tpam_env.so\n"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Validation of the jumps in a [`ResolvedStack`]
+//!
+//! A `value=N` action jumps over the next `N` rules of the stack. Linux-PAM counts the rules after
+//! all `include` rules are expanded, while a `substack` counts as a single rule. A jump that is
+//! written down next to an `include` therefore depends on the number of rules in the included
+//! service. This module computes where every jump actually lands.
+
+use std::fmt::Display;
+
+use crate::{Action, Control, ResolvedStack, RuleOrigin, StackEntry, Value};
+
+/// Jump of a `value=N` action to a concrete target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jump<'a> {
+    origin: RuleOrigin<'a>,
+    value: Value,
+    distance: u32,
+    target: JumpTarget<'a>,
+}
+
+/// Place where a [`Jump`] lands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpTarget<'a> {
+    /// The rule at this location is executed next
+    Rule(RuleOrigin<'a>),
+    /// The jump skips all remaining rules of the stack
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JumpError<'a> {
+    /// The jump skips more rules than there are left in the stack
+    PastEnd {
+        origin: RuleOrigin<'a>,
+        value: Value,
+        distance: u32,
+    },
+    /// The jump lands after the first rule of an expanded `include`
+    IntoInclude {
+        origin: RuleOrigin<'a>,
+        value: Value,
+        distance: u32,
+        target: RuleOrigin<'a>,
+        include: RuleOrigin<'a>,
+    },
+}
+
+impl<'a> Jump<'a> {
+    /// Get the location of the rule that jumps
+    pub fn origin(&self) -> RuleOrigin<'a> {
+        self.origin
+    }
+
+    /// Get the return value for which the rule jumps
+    pub fn value(&self) -> Value {
+        self.value
+    }
+
+    /// Get the number of rules that are jumped over
+    pub fn distance(&self) -> u32 {
+        self.distance
+    }
+
+    /// Get where the jump lands
+    pub fn target(&self) -> JumpTarget<'a> {
+        self.target
+    }
+}
+
+impl<'a> JumpError<'a> {
+    /// Get the location of the rule that jumps
+    pub fn origin(&self) -> RuleOrigin<'a> {
+        match self {
+            JumpError::PastEnd { origin, .. } | JumpError::IntoInclude { origin, .. } => *origin,
+        }
+    }
+}
+
+impl Display for JumpError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JumpError::PastEnd {
+                origin,
+                value,
+                distance,
+            } => write!(
+                f,
+                "`{}={}` of rule {} of `{}` jumps past the end of the stack",
+                value,
+                distance,
+                origin.index(),
+                origin.service()
+            ),
+            JumpError::IntoInclude {
+                origin,
+                value,
+                distance,
+                target,
+                include,
+            } => write!(
+                f,
+                "`{}={}` of rule {} of `{}` jumps to rule {} of `{}` in the middle of the rules \
+                 included by rule {} of `{}`",
+                value,
+                distance,
+                origin.index(),
+                origin.service(),
+                target.index(),
+                target.service(),
+                include.index(),
+                include.service()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for JumpError<'_> {}
+
+impl<'a> ResolvedStack<'a> {
+    /// Compute the target of every jump in this stack and its substacks
+    ///
+    /// A jump may land on any rule that is written in the same service as the jumping rule or in
+    /// a service that includes it, on the first rule of an expanded `include` or at the end of
+    /// the stack. Landing anywhere else is a [`JumpError`].
+    pub fn jumps(&self) -> Vec<Result<Jump<'a>, JumpError<'a>>> {
+        let mut jumps = Vec::new();
+        let mut level_jumps = self.level_jumps().into_iter().peekable();
+
+        for (i, entry) in self.entries().iter().enumerate() {
+            while let Some((_, jump)) = level_jumps.next_if(|(from, _)| *from == i) {
+                jumps.push(jump);
+            }
+
+            if let StackEntry::Substack { stack, .. } = entry {
+                jumps.extend(stack.jumps());
+            }
+        }
+
+        jumps
+    }
+
+    /// Compute the target of every jump in this stack, without its substacks
+    ///
+    /// Every jump is returned together with the index of the entry that jumps.
+    pub(crate) fn level_jumps(&self) -> Vec<(usize, Result<Jump<'a>, JumpError<'a>>)> {
+        let entries = self.entries();
+        let mut jumps = Vec::new();
+
+        for (i, entry) in entries.iter().enumerate() {
+            let Some(Control::Selection(selection)) = entry.rule().control() else {
+                continue;
+            };
+
+            for item in selection.items() {
+                let Action::JumpOver(distance) = item.action() else {
+                    continue;
+                };
+
+                jumps.push((i, jump(entries, i, item.value(), distance)));
+            }
+        }
+
+        jumps
+    }
+}
+
+/// Find where the jump of `distance` rules for `value` from entry `from` lands
+fn jump<'a>(
+    entries: &[StackEntry<'a>],
+    from: usize,
+    value: Value,
+    distance: u32,
+) -> Result<Jump<'a>, JumpError<'a>> {
+    let origin = entries[from].origin();
+    let target = from + distance as usize + 1;
+
+    let target_entry = match entries.get(target) {
+        Some(target_entry) => target_entry,
+        None if target == entries.len() => {
+            return Ok(Jump {
+                origin,
+                value,
+                distance,
+                target: JumpTarget::End,
+            })
+        }
+        None => {
+            return Err(JumpError::PastEnd {
+                origin,
+                value,
+                distance,
+            })
+        }
+    };
+
+    let from_scope = entries[from].included_by();
+    let target_scope = target_entry.included_by();
+    let common = from_scope
+        .iter()
+        .zip(target_scope)
+        .take_while(|(left, right)| left == right)
+        .count();
+
+    // A target that is not written in the service of the jumping rule or in a service that
+    // includes it, has to be the first rule of the include that it is part of
+    if common < target_scope.len()
+        && target > 0
+        && entries[target - 1]
+            .included_by()
+            .starts_with(&target_scope[..=common])
+    {
+        return Err(JumpError::IntoInclude {
+            origin,
+            value,
+            distance,
+            target: target_entry.origin(),
+            include: target_scope[common],
+        });
+    }
+
+    Ok(Jump {
+        origin,
+        value,
+        distance,
+        target: JumpTarget::Rule(target_entry.origin()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{Domain, PamConfig, ReturnCode};
+
+    const PAM_CONF: &str = r#"
+login       auth    [success=2 default=ignore]  pam_unix.so
+login       auth    include                     common-auth
+login       auth    requisite                   pam_deny.so
+login       auth    [success=1 default=ignore]  pam_sss.so
+login       auth    required                    pam_env.so
+login       auth    include                     common-auth
+login       auth    [default=3]                 pam_faildelay.so
+login       auth    required                    pam_permit.so
+common-auth auth    required                    pam_env.so
+common-auth auth    [success=1 default=ignore]  pam_unix.so
+common-auth auth    requisite                   pam_deny.so
+"#;
+
+    #[test]
+    fn targets() {
+        let config = PamConfig::from_str(PAM_CONF).unwrap();
+        let stack = config.resolve("login", Domain::Auth).unwrap();
+        let jumps = stack.jumps();
+
+        assert_eq!(jumps.len(), 5);
+
+        // Linux-PAM counts the included rules, so this lands in the middle of `common-auth`
+        assert_eq!(
+            jumps[0],
+            Err(JumpError::IntoInclude {
+                origin: stack.entries()[0].origin(),
+                value: Value::ReturnCode(ReturnCode::Success),
+                distance: 2,
+                target: stack.entries()[3].origin(),
+                include: stack.entries()[1].included_by()[0],
+            })
+        );
+
+        // Jumping within an included service
+        let jump = jumps[1].as_ref().unwrap();
+        let JumpTarget::Rule(target) = jump.target() else {
+            panic!("Expected a rule");
+        };
+        assert_eq!((target.service(), target.index()), ("login", 2));
+
+        // Jumping to the first rule of an include
+        let jump = jumps[2].as_ref().unwrap();
+        let JumpTarget::Rule(target) = jump.target() else {
+            panic!("Expected a rule");
+        };
+        assert_eq!((target.service(), target.index()), ("common-auth", 0));
+
+        assert!(jumps[3].is_ok());
+
+        assert!(matches!(
+            jumps[4],
+            Err(JumpError::PastEnd { distance: 3, .. })
+        ));
+        assert_eq!(
+            jumps[4].as_ref().unwrap_err().to_string(),
+            "`default=3` of rule 6 of `login` jumps past the end of the stack"
+        );
+    }
+
+    #[test]
+    fn end_and_substack() {
+        let pam_conf = r#"
+login       auth    [success=1 default=ignore]  pam_unix.so
+login       auth    substack                    common-auth
+common-auth auth    [success=2 default=ignore]  pam_unix.so
+common-auth auth    required                    pam_env.so
+"#;
+        let config = PamConfig::from_str(pam_conf).unwrap();
+        let stack = config.resolve("login", Domain::Auth).unwrap();
+        let jumps = stack.jumps();
+
+        assert_eq!(jumps.len(), 2);
+        assert_eq!(jumps[0].as_ref().unwrap().target(), JumpTarget::End);
+
+        // A substack is a stack on its own, so its jumps cannot leave it
+        assert!(matches!(
+            jumps[1],
+            Err(JumpError::PastEnd { distance: 2, .. })
+        ));
+    }
+}
@@ -6,54 +6,145 @@
 
 use std::borrow::Cow;
 use std::collections::BTreeMap;
-use std::fs::{self, File, ReadDir};
+use std::fmt::Display;
+use std::fs::{File, ReadDir};
 use std::io::{self, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 
+mod borrowed;
+mod builder;
 mod control;
+mod diff;
+mod editor;
+mod jump;
+mod lint;
 mod management_group;
 mod module_arguments;
 mod module_path;
+mod openpam;
 mod parsing;
+mod raw;
+mod reader;
+mod resolve;
 mod return_code;
-
-pub use self::control::Control;
-use self::control::ControlParseError;
+mod schema;
+mod search_path;
+#[cfg(feature = "serde")]
+pub mod serialize;
+mod span;
+mod syntax;
+#[cfg(feature = "watch")]
+mod watch;
+mod writer;
+
+pub use self::borrowed::{
+    PackedRuleRefIterator, PamRuleRef, RuleContentRef, SeparatedRuleRefIterator,
+};
+pub use self::builder::{BuildError, PamRuleBuilder, SelectionBuilder};
+pub use self::control::{Action, Control, ControlParseError, Selection, SelectionItem, Value};
+pub use self::diff::{Change, ChangeKind, DiffRule, Impact, ServiceDiff};
+pub use self::editor::{EditError, ServiceEditor};
+pub use self::jump::{Jump, JumpError, JumpTarget};
+pub use self::lint::{Lint, LintCode, Severity};
 pub use self::management_group::Domain;
-pub use self::module_arguments::ModuleArgument;
-pub use self::module_path::ModulePath;
+pub use self::module_arguments::{ModuleArgument, ModuleArgumentRef};
+pub use self::module_path::{ModuleLocation, ModulePath, ModuleSearchPath};
+pub use self::openpam::{OpenPamPackedRuleIterator, OpenPamSeparatedRuleIterator};
 use self::parsing::*;
+pub use self::raw::{RawContent, RawFault, RawRule, RawRuleIterator, RawService};
+pub use self::reader::{PackedRuleReader, ReadLimits, SeparatedRuleReader};
+pub use self::resolve::{
+    ResolveError, ResolvedStack, RuleOrigin, ServiceLookup, StackEntry, MAX_INCLUDE_DEPTH,
+};
 pub use self::return_code::ReturnCode;
+pub use self::schema::{
+    ArgumentError, ArgumentErrorKind, ArgumentKind, ArgumentSchema, ArgumentValue, ModuleSchema,
+    PamAccessArguments, PamDenyArguments, PamEnvArguments, PamFaildelayArguments,
+    PamFaillockArguments, PamKeyinitArguments, PamLimitsArguments, PamLoginuidArguments,
+    PamNologinArguments, PamPermitArguments, PamPwqualityArguments, PamRootokArguments,
+    PamSucceedIfArguments, PamSystemdHomeArguments, PamTimeArguments, PamUmaskArguments,
+    PamUnixArguments, PamWheelArguments, SchemaRegistry, TypedArguments,
+};
+pub use self::search_path::SearchPath;
+pub use self::span::Span;
+pub use self::syntax::{RuleNode, SyntaxNode, SyntaxToken, SyntaxTree, TokenKind};
+#[cfg(feature = "watch")]
+pub use self::watch::{ConfigWatcher, ReloadEvent};
+pub use self::writer::PamWriter;
 
 const PAM_CONF_PATH: &'static str = "/etc/pam.conf";
 const PAM_D_PATH: &'static str = "/etc/pam.d";
+const INCLUDE_DIRECTIVE: &str = "@include";
 
 /// Configuration environment present on a system consisting of several services
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PamConfig {
     services: Vec<PamService>,
 }
 
 /// Named set of [`PamRule`]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PamService {
     name: String,
+    layer: Option<PathBuf>,
     rules: Vec<PamRule>,
 }
 
 /// Single line a PAM configuration file
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PamRule {
+    span: Span,
     is_logging_enabled: bool,
-    domain: Domain,
-    control: Control,
-    module_path: ModulePath,
-    module_arguments: Vec<ModuleArgument>,
+    domain: Option<Domain>,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    content: RuleContent,
+}
+
+/// What a [`PamRule`] does
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum RuleContent {
+    /// Use the rules of another service
+    ServiceInclusion {
+        method: InclusionMethod,
+        service: String,
+    },
+    /// Call a module
+    Entry {
+        control: Control,
+        module_path: ModulePath,
+        module_arguments: Vec<ModuleArgument>,
+    },
+}
+
+/// How a [`RuleContent::ServiceInclusion`] uses the rules of another service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InclusionMethod {
+    /// `include` control, which adds the rules of one domain of the other service to the stack
+    Include,
+    /// `substack` control, which runs the rules of one domain of the other service as a
+    /// separate stack
+    Substack,
+    /// Debian-style `@include` directive, which adds the rules of all domains of the other
+    /// service to the stack
+    IncludeDirective,
+}
+
+/// Syntax error together with the location in the source where it occurred
+#[derive(Debug)]
+pub struct PamConfigSyntaxError {
+    kind: PamConfigSyntaxErrorKind,
+    span: Span,
 }
 
 #[derive(Debug)]
-pub enum PamConfigSyntaxError {
+pub enum PamConfigSyntaxErrorKind {
     HasNewLine,
     CommentLine,
     EmptyLine,
@@ -63,6 +154,9 @@ pub enum PamConfigSyntaxError {
     WrongControl(ControlParseError),
     WrongModulePath(String),
     WrongModuleArgs(String),
+
+    UnclosedQuote,
+    WrongServiceName(String),
 }
 
 #[derive(Debug)]
@@ -71,8 +165,87 @@ pub enum PamConfigError {
     Io(io::Error),
     NotAFilename,
     NonUTF8Filename,
+    /// Error that occurred while reading a specific file
+    InFile(PathBuf, Box<PamConfigError>),
+    /// A line is longer than [`ReadLimits::max_line_length`]
+    LineTooLong {
+        line: usize,
+        max_length: usize,
+    },
+    /// There are more rules than [`ReadLimits::max_rules`]
+    TooManyRules(usize),
+}
+
+impl PamConfigSyntaxError {
+    pub(crate) fn new(kind: PamConfigSyntaxErrorKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Get what went wrong
+    pub fn kind(&self) -> &PamConfigSyntaxErrorKind {
+        &self.kind
+    }
+
+    /// Get where it went wrong
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Render a rustc-style diagnostic with a caret under the offending token
+    ///
+    /// The `source` has to be the same source that was parsed.
+    pub fn render(&self, source: &str) -> String {
+        self.span.render(source, &self.kind)
+    }
+}
+
+impl Display for PamConfigSyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+impl std::error::Error for PamConfigSyntaxError {}
+
+impl Display for PamConfigSyntaxErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use PamConfigSyntaxErrorKind::*;
+
+        match self {
+            HasNewLine => f.write_str("unexpected new line"),
+            CommentLine => f.write_str("unexpected comment line"),
+            EmptyLine => f.write_str("unexpected empty line"),
+            UnclosedBracket => f.write_str("unclosed bracket"),
+            WrongDomain(domain) => write!(f, "unknown domain `{}`", domain),
+            WrongControl(error) => error.fmt(f),
+            WrongModulePath(path) => write!(f, "invalid module path `{}`", path),
+            WrongModuleArgs(args) => write!(f, "invalid module argument `{}`", args),
+            UnclosedQuote => f.write_str("unclosed quote"),
+            WrongServiceName(name) => write!(f, "invalid service name `{}`", name),
+        }
+    }
 }
 
+impl Display for PamConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use PamConfigError::*;
+
+        match self {
+            Syntax(error) => error.fmt(f),
+            Io(error) => error.fmt(f),
+            NotAFilename => f.write_str("path does not point to a file"),
+            NonUTF8Filename => f.write_str("file name is not valid UTF-8"),
+            InFile(path, error) => write!(f, "{}: {}", path.display(), error),
+            LineTooLong { line, max_length } => {
+                write!(f, "line {} is longer than {} bytes", line, max_length)
+            }
+            TooManyRules(max_rules) => write!(f, "more than {} rules", max_rules),
+        }
+    }
+}
+
+impl std::error::Error for PamConfigError {}
+
 impl From<io::Error> for PamConfigError {
     fn from(value: io::Error) -> Self {
         Self::Io(value)
@@ -85,6 +258,35 @@ impl From<PamConfigSyntaxError> for PamConfigError {
     }
 }
 
+impl FromStr for InclusionMethod {
+    type Err = ();
+
+    /// Parse the `include` or `substack` control
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "include" => Ok(Self::Include),
+            "substack" => Ok(Self::Substack),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<InclusionMethod> for &'static str {
+    fn from(value: InclusionMethod) -> Self {
+        match value {
+            InclusionMethod::Include => "include",
+            InclusionMethod::Substack => "substack",
+            InclusionMethod::IncludeDirective => INCLUDE_DIRECTIVE,
+        }
+    }
+}
+
+impl Display for InclusionMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <&'static str>::from(*self).fmt(f)
+    }
+}
+
 impl PamConfig {
     /// Get the associated [`PamService`]s
     pub fn services(&self) -> &[PamService] {
@@ -96,12 +298,35 @@ impl PamConfig {
     /// On Linux, this is usually a file in the `/etc/pam.conf` file. To parse the a service from
     /// the `/etc/pam.d` directory look at [`PamService::from_file`]. 
     pub fn from_file(path: impl AsRef<Path>) -> Result<PamConfig, PamConfigError> {
+        let path = path.as_ref();
+        let mut file = File::open(path)?;
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        Ok(Self::from_packed_iter(PackedRuleIterator::new(
+            &contents,
+            Some(Arc::from(path)),
+        ))?)
+    }
+
+    /// Leniently read a [`PamConfig`] from a packed configuration file
+    ///
+    /// This is the same as [`PamConfig::from_file`], except that lines with a syntax error are
+    /// skipped and returned next to the [`PamConfig`] instead of aborting the parse.
+    pub fn from_file_lenient(
+        path: impl AsRef<Path>,
+    ) -> Result<(PamConfig, Vec<PamConfigSyntaxError>), PamConfigError> {
+        let path = path.as_ref();
         let mut file = File::open(path)?;
 
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
 
-        Ok(Self::from_str(&contents)?)
+        Ok(Self::from_packed_iter_lenient(PackedRuleIterator::new(
+            &contents,
+            Some(Arc::from(path)),
+        )))
     }
 
     /// Read a [`PamConfig`] from all service files in a directory
@@ -116,24 +341,59 @@ impl PamConfig {
         Ok(PamConfig { services })
     }
 
+    /// Leniently read a [`PamConfig`] from all service files in a directory
+    ///
+    /// This is the same as [`PamConfig::from_dir`], except that it does not stop at the first
+    /// error. Lines with a syntax error are skipped and files that cannot be read are left out.
+    /// Every problem that was found is returned next to the [`PamConfig`].
+    pub fn from_dir_lenient(dir: ReadDir) -> (PamConfig, Vec<PamConfigError>) {
+        let mut services = Vec::new();
+        let mut errors = Vec::new();
+
+        for dir_entry in dir {
+            let path = match dir_entry {
+                Ok(dir_entry) => dir_entry.path(),
+                Err(err) => {
+                    errors.push(PamConfigError::Io(err));
+                    continue;
+                }
+            };
+
+            match PamService::from_file_lenient(&path) {
+                Ok((service, syntax_errors)) => {
+                    services.push(service);
+                    errors.extend(syntax_errors.into_iter().map(PamConfigError::Syntax));
+                }
+                Err(err) => errors.push(PamConfigError::InFile(path, Box::new(err))),
+            }
+        }
+
+        (PamConfig { services }, errors)
+    }
+
     /// Read a [`PamConfig`] from the current system
     ///
-    /// If `/etc/pam.d` exists, the service files there are used. If `/etc/pam.d` does not exist,
-    /// the `/etc/pam.conf` is used.
+    /// If `/etc/pam.d` or one of the vendor directories `/usr/lib/pam.d` and `/usr/etc/pam.d`
+    /// exists, the service files there are used. Otherwise, the `/etc/pam.conf` is used. See
+    /// [`SearchPath`] for the details.
     pub fn from_system() -> Result<PamConfig, PamConfigError> {
-        // From the Linux-PAM manual page:
-        //
-        // > This dynamic configuration is set by the contents of the single Linux-PAM
-        // > configuration file /etc/pam.conf. Alternatively, the configuration can be set by
-        // > individual configuration files located in the /etc/pam.d/ directory. The presence of
-        // > this directory will cause Linux-PAM to ignore /etc/pam.conf.
-
-        let pam_d_path = Path::new(PAM_D_PATH);
-        if pam_d_path.try_exists()? && pam_d_path.is_dir() {
-            Self::from_dir(fs::read_dir(pam_d_path)?)
-        } else {
-            Self::from_file(PAM_CONF_PATH)
-        }
+        Self::from_search_path(&SearchPath::default())
+    }
+
+    /// Leniently read a [`PamConfig`] from the current system
+    ///
+    /// This uses the same files as [`PamConfig::from_system`], but reads them in the same way as
+    /// [`PamConfig::from_dir_lenient`] or [`PamConfig::from_file_lenient`]. Only failing to read
+    /// the configuration directories or file itself is an error.
+    pub fn from_system_lenient() -> Result<(PamConfig, Vec<PamConfigError>), PamConfigError> {
+        Self::from_search_path_lenient(&SearchPath::default())
+    }
+
+    /// Leniently read a [`PamConfig`] from a [`&str`] in the packed configuration syntax
+    ///
+    /// Lines with a syntax error are skipped and returned next to the [`PamConfig`].
+    pub fn from_str_lenient(s: &str) -> (PamConfig, Vec<PamConfigSyntaxError>) {
+        Self::from_packed_iter_lenient(PamRule::packed_iter(s))
     }
 }
 
@@ -141,14 +401,35 @@ impl FromStr for PamConfig {
     type Err = PamConfigSyntaxError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let packed_rules: Vec<Result<(String, PamRule), PamConfigSyntaxError>> =
-            PamRule::packed_iter(s).collect();
+        Self::from_packed_iter(PamRule::packed_iter(s))
+    }
+}
+
+impl PamConfig {
+    fn from_packed_iter(iter: PackedRuleIterator) -> Result<Self, PamConfigSyntaxError> {
+        let packed_rules =
+            iter.collect::<Result<Vec<(String, PamRule)>, PamConfigSyntaxError>>()?;
+        Ok(Self::from_packed_rules(packed_rules))
+    }
+
+    fn from_packed_iter_lenient(iter: PackedRuleIterator) -> (Self, Vec<PamConfigSyntaxError>) {
+        let mut packed_rules = Vec::new();
+        let mut errors = Vec::new();
+
+        for packed_rule in iter {
+            match packed_rule {
+                Ok(packed_rule) => packed_rules.push(packed_rule),
+                Err(err) => errors.push(err),
+            }
+        }
 
+        (Self::from_packed_rules(packed_rules), errors)
+    }
+
+    fn from_packed_rules(packed_rules: Vec<(String, PamRule)>) -> Self {
         // Form a Map from "Service Name" --> Vec<PamRule> 
         let mut services: BTreeMap<String, Vec<PamRule>> = BTreeMap::new();
-        for packed_rule in packed_rules.into_iter() {
-            let (service_name, rule) = packed_rule?;
-
+        for (service_name, rule) in packed_rules.into_iter() {
             if let Some(rules) = services.get_mut(&service_name) {
                 rules.push(rule);
             } else {
@@ -158,10 +439,14 @@ impl FromStr for PamConfig {
 
         let services = services
             .into_iter()
-            .map(|(name, rules)| PamService { name, rules })
+            .map(|(name, rules)| PamService {
+                name,
+                layer: None,
+                rules,
+            })
             .collect();
 
-        Ok(Self { services })
+        Self { services }
     }
 }
 
@@ -181,10 +466,22 @@ impl PamService {
         self.rules.push(rule)
     }
 
+    /// Get the configuration directory this service was read from
+    ///
+    /// This is `None` for services that were read from a packed configuration file or that were
+    /// created with [`PamService::new`].
+    pub fn layer(&self) -> Option<&Path> {
+        self.layer.as_deref()
+    }
+
     /// Create a new [`PamService`]
     pub fn new(name: &str, rules: Vec<PamRule>) -> Self {
         let name = String::from(name);
-        Self { name, rules }
+        Self {
+            name,
+            layer: None,
+            rules,
+        }
     }
 
     /// Read a [`PamService`] from a service file
@@ -192,9 +489,45 @@ impl PamService {
     /// The file-name is used as the service name. On Linux, this is usually a file in the
     /// `/etc/pam.d` directory. To parse the `/etc/pam.conf` file look at [`PamConfig::from_file`]. 
     pub fn from_file(path: impl AsRef<Path>) -> Result<PamService, PamConfigError> {
-        use PamConfigError::{NonUTF8Filename, NotAFilename};
+        let path = path.as_ref();
+        let (name, contents) = Self::read_file(path)?;
+
+        // Parse the file contents
+        let rules = SeparatedRuleIterator::new(&contents, Some(Arc::from(path)))
+            .collect::<Result<Vec<PamRule>, PamConfigSyntaxError>>()?;
+
+        Ok(Self {
+            name,
+            layer: path.parent().map(Path::to_path_buf),
+            rules,
+        })
+    }
 
+    /// Leniently read a [`PamService`] from a service file
+    ///
+    /// This is the same as [`PamService::from_file`], except that lines with a syntax error are
+    /// skipped and returned next to the [`PamService`] instead of aborting the parse.
+    pub fn from_file_lenient(
+        path: impl AsRef<Path>,
+    ) -> Result<(PamService, Vec<PamConfigSyntaxError>), PamConfigError> {
         let path = path.as_ref();
+        let (name, contents) = Self::read_file(path)?;
+
+        let (rules, errors) =
+            partition_rules(SeparatedRuleIterator::new(&contents, Some(Arc::from(path))));
+
+        let service = Self {
+            name,
+            layer: path.parent().map(Path::to_path_buf),
+            rules,
+        };
+
+        Ok((service, errors))
+    }
+
+    /// Read the service name and the contents of a service file
+    fn read_file(path: &Path) -> Result<(String, String), PamConfigError> {
+        use PamConfigError::{NonUTF8Filename, NotAFilename};
 
         let mut file = File::open(path)?;
         let name = path
@@ -207,10 +540,7 @@ impl PamService {
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
 
-        // Parse the file contents
-        let rules = Self::from_str(&contents)?;
-
-        Ok(Self { name, rules })
+        Ok((name, contents))
     }
 
     /// Read a vector of [`PamRule`]s from a [`&str`]
@@ -238,9 +568,24 @@ impl PamService {
     pub fn from_str(s: &str) -> Result<Vec<PamRule>, PamConfigSyntaxError> {
         PamRule::separated_iter(s).collect()
     }
+
+    /// Leniently read a vector of [`PamRule`]s from a [`&str`]
+    ///
+    /// This is the same as [`PamService::from_str`], except that lines with a syntax error are
+    /// skipped. All syntax errors are returned next to the rules that could be parsed.
+    pub fn from_str_lenient(s: &str) -> (Vec<PamRule>, Vec<PamConfigSyntaxError>) {
+        partition_rules(PamRule::separated_iter(s))
+    }
 }
 
 impl PamRule {
+    /// Get the location of this rule in its source
+    ///
+    /// This spans the whole rule including any line continuations.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
     /// Get whether to log for this rule or not
     pub fn is_logging_enabled(&self) -> bool {
         self.is_logging_enabled
@@ -249,38 +594,79 @@ impl PamRule {
     /// Get the [`Domain`]
     ///
     /// In the PAM configuration this can have one of 4 values: 'account', 'auth', 'password' or
-    /// 'session'
-    pub fn domain(&self) -> Domain {
+    /// 'session'. This is `None` for an `@include` directive, which includes all domains of
+    /// another service.
+    pub fn domain(&self) -> Option<Domain> {
         self.domain
     }
 
-    /// Get the control parameters
-    pub fn control(&self) -> &Control {
-        &self.control
+    /// Get whether this rule is a Debian-style `@include` directive
+    ///
+    /// An `@include` directive includes the rules of all domains of the service named by
+    /// [`PamRule::included_service`]. It has no domain.
+    pub fn is_include_directive(&self) -> bool {
+        self.inclusion_method() == Some(InclusionMethod::IncludeDirective)
+    }
+
+    /// Get what this rule does
+    pub fn content(&self) -> &RuleContent {
+        &self.content
+    }
+
+    /// Get how this rule includes another service, which is `None` for a module rule
+    pub fn inclusion_method(&self) -> Option<InclusionMethod> {
+        match &self.content {
+            RuleContent::ServiceInclusion { method, .. } => Some(*method),
+            RuleContent::Entry { .. } => None,
+        }
+    }
+
+    /// Get the name of the service this rule includes, which is `None` for a module rule
+    pub fn included_service(&self) -> Option<&str> {
+        match &self.content {
+            RuleContent::ServiceInclusion { service, .. } => Some(service),
+            RuleContent::Entry { .. } => None,
+        }
+    }
+
+    /// Get the control parameters, which is `None` for a rule that includes another service
+    pub fn control(&self) -> Option<&Control> {
+        match &self.content {
+            RuleContent::Entry { control, .. } => Some(control),
+            RuleContent::ServiceInclusion { .. } => None,
+        }
     }
 
-    /// Get the module path
-    pub fn module_path(&self) -> &ModulePath {
-        &self.module_path
+    /// Get the module path, which is `None` for a rule that includes another service
+    pub fn module_path(&self) -> Option<&ModulePath> {
+        match &self.content {
+            RuleContent::Entry { module_path, .. } => Some(module_path),
+            RuleContent::ServiceInclusion { .. } => None,
+        }
     }
 
-    /// Get the module argument 
+    /// Get the module arguments, which is empty for a rule that includes another service
     pub fn module_arguments(&self) -> &[ModuleArgument] {
-        &self.module_arguments
+        match &self.content {
+            RuleContent::Entry {
+                module_arguments, ..
+            } => module_arguments,
+            RuleContent::ServiceInclusion { .. } => &[],
+        }
     }
 
     /// Create an iterator over rules in the packed configuration format
     ///
     /// The packed configuration format is the same format as used in `/etc/pam.conf`.
-    pub fn packed_iter(s: &str) -> PackedRuleIterator {
-        PackedRuleIterator(s)
+    pub fn packed_iter(s: &str) -> PackedRuleIterator<'_> {
+        PackedRuleIterator::new(s, None)
     }
 
     /// Create an iterator over rules in the separated configuration format
     ///
     /// The separated configuration format is the same format as used in `/etc/pam.d`.
-    pub fn separated_iter(s: &str) -> SeparatedRuleIterator {
-        SeparatedRuleIterator(s)
+    pub fn separated_iter(s: &str) -> SeparatedRuleIterator<'_> {
+        SeparatedRuleIterator::new(s, None)
     }
 }
 
@@ -300,13 +686,16 @@ impl PamRule {
 /// * `module`: [`ModulePath`]
 /// * `module-arguments`: multiple [`ModuleArgument`]s
 ///
+/// Instead of the `domain` and the rest of the line, a Debian-style `@include other-service`
+/// directive can be given.
+///
 /// Lines that only contain spaces and tabs or lines that start with `#` are ignored. 
 ///
 /// # Note
 ///
 /// If a error is found on a line, the iterator will move to the next line. The iterator is stopped
 /// when the end of file (`EOF`) is reached.
-pub struct PackedRuleIterator<'a>(&'a str);
+pub struct PackedRuleIterator<'a>(EscapedLines<'a>);
 
 /// Iterator over the separated configuration format
 ///
@@ -323,24 +712,35 @@ pub struct PackedRuleIterator<'a>(&'a str);
 /// * `module`: [`ModulePath`]
 /// * `module-arguments`: multiple [`ModuleArgument`]s
 ///
+/// Instead of a rule, a line can also be a Debian-style `@include other-service` directive.
+///
 /// Lines that only contain spaces and tabs or lines that start with `#` are ignored. 
 ///
 /// # Note
 ///
 /// If a error is found on a line, the iterator will move to the next line. The iterator is stopped
 /// when the end of file (`EOF`) is reached.
-pub struct SeparatedRuleIterator<'a>(&'a str);
+pub struct SeparatedRuleIterator<'a>(EscapedLines<'a>);
+
+impl<'a> PackedRuleIterator<'a> {
+    fn new(s: &'a str, path: Option<Arc<Path>>) -> Self {
+        Self(EscapedLines::new(s, path))
+    }
+}
+
+impl<'a> SeparatedRuleIterator<'a> {
+    fn new(s: &'a str, path: Option<Arc<Path>>) -> Self {
+        Self(EscapedLines::new(s, path))
+    }
+}
 
 impl<'a> Iterator for PackedRuleIterator<'a> {
     type Item = Result<(String, PamRule), PamConfigSyntaxError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let Self(s) = self;
-        let (s, escaped_line) = take_escaped_line(s)?;
-
-        self.0 = s;
-
-        Some(take_packed_rule(&escaped_line))
+        let escaped_line = self.0.next()?;
+        let rule = take_packed_rule(&escaped_line, &escaped_line.text);
+        Some(rule.map(|(service_name, rule)| (service_name.into_owned(), rule.into_owned())))
     }
 }
 
@@ -348,122 +748,225 @@ impl<'a> Iterator for SeparatedRuleIterator<'a> {
     type Item = Result<PamRule, PamConfigSyntaxError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let Self(s) = self;
-        let (s, escaped_line) = take_escaped_line(s)?;
+        let escaped_line = self.0.next()?;
+        Some(take_rule(&escaped_line, &escaped_line.text).map(PamRuleRef::into_owned))
+    }
+}
 
-        self.0 = s;
+fn partition_rules(iter: SeparatedRuleIterator) -> (Vec<PamRule>, Vec<PamConfigSyntaxError>) {
+    let mut rules = Vec::new();
+    let mut errors = Vec::new();
 
-        Some(take_separated_rule(&escaped_line))
+    for rule in iter {
+        match rule {
+            Ok(rule) => rules.push(rule),
+            Err(err) => errors.push(err),
+        }
     }
-}
 
-fn take_packed_rule(escaped_line: &str) -> Result<(String, PamRule), PamConfigSyntaxError> {
-    let (escaped_line, service_name) = take_service_name(escaped_line)?;
-    let (escaped_line, _) = skip_whitespace(escaped_line);
-    let rule = take_separated_rule(escaped_line)?;
+    (rules, errors)
+}
 
-    let service_name = String::from(service_name);
+fn take_packed_rule<'s>(
+    line: &EscapedLine,
+    s: &'s str,
+) -> Result<(Cow<'s, str>, PamRuleRef<'s>), PamConfigSyntaxError> {
+    let (s, service_name) = take_service_name(line, s)?;
+    let (s, _) = skip_whitespace(s);
+    let rule = take_rule(line, s)?;
 
     Ok((service_name, rule))
 }
 
-fn take_separated_rule(escaped_line: &str) -> Result<PamRule, PamConfigSyntaxError> {
-    let (escaped_line, domain, is_logging_enabled) = take_domain(escaped_line)?;
-    let (escaped_line, _) = skip_whitespace(escaped_line);
-    let (escaped_line, control) = take_control(escaped_line)?;
-    let (escaped_line, _) = skip_whitespace(escaped_line);
-    let (escaped_line, module_path) = take_module_path(escaped_line)?;
-    let (escaped_line, _) = skip_whitespace(escaped_line);
-    let module_arguments = take_module_arguments(escaped_line)?;
+fn take_rule<'s>(line: &EscapedLine, s: &'s str) -> Result<PamRuleRef<'s>, PamConfigSyntaxError> {
+    if let Some(s) = s.strip_prefix(INCLUDE_DIRECTIVE) {
+        if s.is_empty() || s.starts_with([' ', '\t']) {
+            return take_service_inclusion(line, s, None, true, InclusionMethod::IncludeDirective);
+        }
+    }
+
+    let (s, domain, is_logging_enabled) = take_domain(line, s)?;
+    let (s, _) = skip_whitespace(s);
+
+    if let Some((s, method)) = take_inclusion_method(s) {
+        return take_service_inclusion(line, s, Some(domain), is_logging_enabled, method);
+    }
+
+    let (s, control) = take_control(line, s)?;
+    let (s, _) = skip_whitespace(s);
+    let (s, module_path) = take_module_path(line, s)?;
+    let (s, _) = skip_whitespace(s);
+    let module_arguments = take_module_arguments(line, s)?;
+
+    Ok(PamRuleRef {
+        span: line.span(0..line.text.len()),
+        domain: Some(domain),
+        is_logging_enabled,
+        content: RuleContentRef::Entry {
+            control,
+            module_path: Cow::Borrowed(module_path),
+            module_arguments,
+        },
+    })
+}
+
+/// Takes an `include` or `substack` control
+fn take_inclusion_method(s: &str) -> Option<(&str, InclusionMethod)> {
+    let (token, after) = till_whitespace(s);
+    let method = InclusionMethod::from_str(token).ok()?;
+    Some((&s[after..], method))
+}
 
-    Ok(PamRule {
+/// Takes the rest of a rule that includes another service after the control or the `@include`
+/// keyword
+fn take_service_inclusion<'s>(
+    line: &EscapedLine,
+    s: &'s str,
+    domain: Option<Domain>,
+    is_logging_enabled: bool,
+    method: InclusionMethod,
+) -> Result<PamRuleRef<'s>, PamConfigSyntaxError> {
+    let (s, _) = skip_whitespace(s);
+    let start = line.offset_of(s);
+    // Linux-PAM ignores anything after the service name, so it is dropped in the same way
+    let (service, _) = till_whitespace(s);
+    if service.is_empty() {
+        return Err(line.error(
+            start..start,
+            PamConfigSyntaxErrorKind::WrongModulePath(service.to_string()),
+        ));
+    }
+    let service = Cow::Borrowed(service);
+
+    Ok(PamRuleRef {
+        span: line.span(0..line.text.len()),
         domain,
         is_logging_enabled,
-        control,
-        module_path,
-        module_arguments,
+        content: RuleContentRef::ServiceInclusion { method, service },
     })
 }
 
-fn take_service_name(s: &str) -> Result<(&str, Cow<str>), PamConfigSyntaxError> {
-    let (service_name, after) = take_string(s).ok_or(PamConfigSyntaxError::UnclosedBracket)?;
+fn take_service_name<'s>(
+    line: &EscapedLine,
+    s: &'s str,
+) -> Result<(&'s str, Cow<'s, str>), PamConfigSyntaxError> {
+    let start = line.offset_of(s);
+    let (service_name, after) = take_string(s).ok_or_else(|| {
+        line.error(
+            start..line.text.len(),
+            PamConfigSyntaxErrorKind::UnclosedBracket,
+        )
+    })?;
     let s = &s[after..];
     Ok((s, service_name))
 }
 
-fn take_domain(s: &str) -> Result<(&str, Domain, bool), PamConfigSyntaxError> {
+fn take_domain<'s>(
+    line: &EscapedLine,
+    s: &'s str,
+) -> Result<(&'s str, Domain, bool), PamConfigSyntaxError> {
     // Take group ('account', 'auth', 'password', 'session')
-    let (domain, after) = till_whitespace(s);
+    let start = line.offset_of(s);
+    let (token, after) = till_whitespace(s);
     let s = &s[after..];
 
     // We disable logging if the '-' is prepended
-    let is_logging_enabled = !domain.starts_with('-');
-    let domain = &domain[if is_logging_enabled { 0 } else { 1 }..];
-    let domain = Domain::from_str(domain)
-        .map_err(|_| PamConfigSyntaxError::WrongDomain(domain.to_string()))?;
+    let is_logging_enabled = !token.starts_with('-');
+    let domain = &token[if is_logging_enabled { 0 } else { 1 }..];
+    let domain = Domain::from_str(domain).map_err(|_| {
+        line.error(
+            start..start + token.len(),
+            PamConfigSyntaxErrorKind::WrongDomain(domain.to_string()),
+        )
+    })?;
 
     Ok((s, domain, is_logging_enabled))
 }
 
-fn take_control(s: &str) -> Result<(&str, Control), PamConfigSyntaxError> {
+fn take_control<'s>(
+    line: &EscapedLine,
+    s: &'s str,
+) -> Result<(&'s str, Control), PamConfigSyntaxError> {
     // Take control parameters
-    let (control, after) = take_control_string(s).ok_or(PamConfigSyntaxError::UnclosedBracket)?;
-    let control = Control::from_str(control).map_err(|e| PamConfigSyntaxError::WrongControl(e))?;
+    let start = line.offset_of(s);
+    let (control, after) = take_control_string(s).ok_or_else(|| {
+        line.error(
+            start..line.text.len(),
+            PamConfigSyntaxErrorKind::UnclosedBracket,
+        )
+    })?;
+    let control = Control::from_str(control)
+        .and_then(|parsed| match parsed {
+            // `binding` only exists in OpenPAM
+            Control::Binding => Err(ControlParseError::UnknownPreset(control.to_string())),
+            parsed => Ok(parsed),
+        })
+        .map_err(|e| {
+            line.error(
+                start..start + control.len(),
+                PamConfigSyntaxErrorKind::WrongControl(e),
+            )
+        })?;
     let s = &s[after..];
 
     Ok((s, control))
 }
 
-fn take_module_path(s: &str) -> Result<(&str, ModulePath), PamConfigSyntaxError> {
+fn take_module_path<'s>(
+    line: &EscapedLine,
+    s: &'s str,
+) -> Result<(&'s str, &'s str), PamConfigSyntaxError> {
     // Take module path
+    let start = line.offset_of(s);
     let (module_path, after) = till_whitespace(s);
     let s = &s[after..];
 
-    let module_path = ModulePath::from_str(module_path)
-        .map_err(|_| PamConfigSyntaxError::WrongModulePath(module_path.to_string()))?;
+    // NOTE: This is the only check of `ModulePath::from_str`, which would allocate.
+    if module_path.is_empty() {
+        return Err(line.error(
+            start..start,
+            PamConfigSyntaxErrorKind::WrongModulePath(module_path.to_string()),
+        ));
+    }
 
     Ok((s, module_path))
 }
 
-fn take_module_arguments(s: &str) -> Result<Vec<ModuleArgument>, PamConfigSyntaxError> {
+fn take_module_arguments<'s>(
+    line: &EscapedLine,
+    s: &'s str,
+) -> Result<Vec<ModuleArgumentRef<'s>>, PamConfigSyntaxError> {
     // Take module arguments. This is the rest of list and basically is an env key-value pair.
+    let start = line.offset_of(s);
     let module_arguments = if s.is_empty() {
         Vec::new()
     } else {
         take_all_strings(s)
-            .ok_or(PamConfigSyntaxError::UnclosedBracket)?
+            .ok_or_else(|| {
+                line.error(
+                    start..line.text.len(),
+                    PamConfigSyntaxErrorKind::UnclosedBracket,
+                )
+            })?
             .into_iter()
-            .map(|s| {
-                ModuleArgument::from_str(&s)
-                    .map_err(|_| PamConfigSyntaxError::WrongModuleArgs(s.to_string()))
+            .map(|(s, range)| {
+                ModuleArgumentRef::parse(s).map_err(|s| {
+                    line.error(
+                        start + range.start..start + range.end,
+                        PamConfigSyntaxErrorKind::WrongModuleArgs(s.into_owned()),
+                    )
+                })
             })
-            .collect::<Result<Vec<ModuleArgument>, PamConfigSyntaxError>>()?
+            .collect::<Result<Vec<ModuleArgumentRef>, PamConfigSyntaxError>>()?
     };
 
     Ok(module_arguments)
 }
 
-fn take_escaped_line(mut s: &str) -> Option<(&str, Cow<str>)> {
-    loop {
-        if s.is_empty() {
-            return None;
-        }
-
-        let (line, leftover) = till_end_of_line(&s);
-        s = &s[leftover..];
-
-        let trimmed = line.trim();
-        if trimmed.is_empty() || trimmed.starts_with('#') {
-            continue;
-        }
-
-        break Some((s, line));
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
 
     #[test]
     fn whitespace_finder() {
@@ -484,6 +987,144 @@ account    requisite    pam-dalkfj.so    pxxxassword=xyz   [axy=salfjdsaljf sdal
         assert!(false);
     }
 
+    #[test]
+    fn rule_spans() {
+        let service_file =
+            "#%PAM-1.0\n\nauth required pam_env.so\nauth sufficient \\\n  pam_unix.so nullok\n";
+        let rules = PamService::from_str(service_file).unwrap();
+
+        let span = rules[0].span();
+        assert_eq!((span.line(), span.column()), (3, 1));
+        assert_eq!(&service_file[span.range()], "auth required pam_env.so");
+
+        let span = rules[1].span();
+        assert_eq!((span.line(), span.column()), (4, 1));
+        assert_eq!(
+            &service_file[span.range()],
+            "auth sufficient \\\n  pam_unix.so nullok"
+        );
+    }
+
+    #[test]
+    fn error_location() {
+        let service_file = "auth required pam_env.so\nauth sufficient \\\n  pam_unix.so [nullok\n";
+        let error = PamService::from_str(service_file).unwrap_err();
+
+        assert!(matches!(
+            error.kind(),
+            PamConfigSyntaxErrorKind::UnclosedBracket
+        ));
+        assert_eq!((error.span().line(), error.span().column()), (3, 15));
+        assert_eq!(&service_file[error.span().range()], "[nullok");
+
+        let service_file = "auth\t[default=xyz] pam_unix.so\n";
+        let error = PamService::from_str(service_file).unwrap_err();
+
+        assert_eq!(error.to_string(), "1:6: unknown action `xyz`");
+        assert_eq!(
+            error.render(service_file),
+            "error: unknown action `xyz`\n \
+             --> 1:6\n  \
+             |\n\
+             1 | auth\t[default=xyz] pam_unix.so\n  \
+             |     \t^^^^^^^^^^^^^"
+        );
+
+        let error = PamService::from_str("-sesion optional pam_motd.so").unwrap_err();
+        assert!(matches!(error.kind(), PamConfigSyntaxErrorKind::WrongDomain(d) if d == "sesion"));
+        assert_eq!(error.span().range(), 0..7);
+    }
+
+    #[test]
+    fn lenient() {
+        let service_file = r#"
+auth       required     pam_env.so
+auth       sufficent    pam_unix.so
+auth       required     pam_deny.so
+acount     required     pam_unix.so
+"#;
+        let (rules, errors) = PamService::from_str_lenient(service_file);
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1].module_path().unwrap().to_string(), "pam_deny.so");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].span().line(), 3);
+        assert_eq!(errors[1].span().line(), 5);
+
+        let pam_conf = r#"
+login   auth    required    pam_env.so
+login   auth    [default=x] pam_unix.so
+sshd    auth    required    pam_env.so
+"#;
+        let (config, errors) = PamConfig::from_str_lenient(pam_conf);
+
+        assert_eq!(config.services().len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors[0].kind(),
+            PamConfigSyntaxErrorKind::WrongControl(ControlParseError::UnknownAction(_))
+        ));
+    }
+
+    #[test]
+    fn lenient_dir() {
+        let dir = std::env::temp_dir().join(format!("pamela-lenient-{}", std::process::id()));
+        fs::create_dir_all(dir.join("subdir")).unwrap();
+        fs::write(dir.join("login"), "auth required pam_env.so\n").unwrap();
+        fs::write(dir.join("login.rpmnew"), "auth requird pam_env.so\n").unwrap();
+
+        let (config, errors) = PamConfig::from_dir_lenient(fs::read_dir(&dir).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut names: Vec<&str> = config.services().iter().map(|s| s.name()).collect();
+        names.sort();
+        assert_eq!(names, ["login", "login.rpmnew"]);
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|error| matches!(
+            error,
+            PamConfigError::Syntax(error) if error.span().path() == Some(&dir.join("login.rpmnew"))
+        )));
+        assert!(errors.iter().any(|error| matches!(
+            error,
+            PamConfigError::InFile(path, _) if path == &dir.join("subdir")
+        )));
+    }
+
+    #[test]
+    fn include_directive() {
+        let service_file = r#"
+@include common-auth
+auth    required    pam_env.so
+@includes common-auth
+@include
+@include common-auth nullok
+"#;
+        let (rules, errors) = PamService::from_str_lenient(service_file);
+
+        assert_eq!(rules.len(), 3);
+        assert!(rules[0].is_include_directive());
+        assert_eq!(rules[0].domain(), None);
+        assert!(rules[0].control().is_none());
+        assert_eq!(rules[0].included_service(), Some("common-auth"));
+        assert_eq!(rules[1].domain(), Some(Domain::Auth));
+
+        // Linux-PAM ignores the tokens after the included service
+        assert_eq!(rules[2].included_service(), Some("common-auth"));
+
+        assert_eq!(errors.len(), 2);
+        assert!(
+            matches!(errors[0].kind(), PamConfigSyntaxErrorKind::WrongDomain(d) if d == "@includes")
+        );
+        assert!(matches!(
+            errors[1].kind(),
+            PamConfigSyntaxErrorKind::WrongModulePath(_)
+        ));
+
+        let rules = PamService::from_str("auth include system-auth foo\n").unwrap();
+        assert_eq!(rules[0].included_service(), Some("system-auth"));
+    }
+
     // pam.conf
     // service      type        control         module-path     module-arguments
     //
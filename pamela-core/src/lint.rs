@@ -0,0 +1,425 @@
+//! Static checks for common mistakes in resolved stacks
+//!
+//! The checks run on a [`ResolvedStack`], so rules that come from included services are checked
+//! in the context where they are actually executed. Every [`Lint`] has a [`Severity`], a stable
+//! [`LintCode`] and the location of the rule it is about.
+
+use std::fmt::Display;
+
+use crate::{
+    Action, Control, Domain, JumpError, ModuleArgument, PamConfig, PamRule, ResolveError,
+    ResolvedStack, RuleOrigin, StackEntry,
+};
+
+const PAM_PERMIT: &str = "pam_permit.so";
+const PAM_DENY: &str = "pam_deny.so";
+
+/// How bad a [`Lint`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The stack probably does not do what was intended
+    Warning,
+    /// The stack is broken or allows access that should not be allowed
+    Error,
+}
+
+/// Kind of mistake that is found by a [`Lint`]
+///
+/// The string representation of a code is stable, so it can be used to filter findings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintCode {
+    /// A jump points past the end of the stack
+    JumpPastEnd,
+    /// A jump lands in the middle of the rules of an `include`
+    JumpIntoInclude,
+    /// A rule can never be reached, because all rules before it end the stack
+    UnreachableRule,
+    /// `pam_permit.so` is `sufficient` before other rules in an `auth` stack
+    PermitSufficient,
+    /// A `password` module accepts empty passwords with `nullok`
+    PasswordNullok,
+    /// A non-empty `auth` stack has no required `pam_deny.so` to fall back on
+    MissingDeny,
+    /// The same rule appears twice in a stack
+    DuplicateRule,
+}
+
+/// Single finding of the linter
+#[derive(Debug, Clone)]
+pub struct Lint<'a> {
+    code: LintCode,
+    domain: Domain,
+    origin: Option<RuleOrigin<'a>>,
+    rule: Option<&'a PamRule>,
+}
+
+impl LintCode {
+    /// Get the [`Severity`] of this kind of mistake
+    pub fn severity(self) -> Severity {
+        use LintCode::*;
+
+        match self {
+            JumpPastEnd | JumpIntoInclude | PermitSufficient => Severity::Error,
+            UnreachableRule | PasswordNullok | MissingDeny | DuplicateRule => Severity::Warning,
+        }
+    }
+}
+
+impl<'a> Lint<'a> {
+    fn new(code: LintCode, domain: Domain, entry: Option<&StackEntry<'a>>) -> Self {
+        Self {
+            code,
+            domain,
+            origin: entry.map(StackEntry::origin),
+            rule: entry.map(StackEntry::rule),
+        }
+    }
+
+    /// Get the kind of mistake
+    pub fn code(&self) -> LintCode {
+        self.code
+    }
+
+    /// Get how bad the mistake is
+    pub fn severity(&self) -> Severity {
+        self.code.severity()
+    }
+
+    /// Get the [`Domain`] of the stack that contains the mistake
+    pub fn domain(&self) -> Domain {
+        self.domain
+    }
+
+    /// Get where the rule with the mistake was written down
+    ///
+    /// This is `None` for mistakes about a whole stack that has no rules.
+    pub fn origin(&self) -> Option<RuleOrigin<'a>> {
+        self.origin
+    }
+
+    /// Get the rule with the mistake
+    ///
+    /// For mistakes about a whole stack this is the last rule of the stack.
+    pub fn rule(&self) -> Option<&'a PamRule> {
+        self.rule
+    }
+}
+
+impl From<Severity> for &'static str {
+    fn from(value: Severity) -> Self {
+        match value {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+impl From<LintCode> for &'static str {
+    fn from(value: LintCode) -> Self {
+        use LintCode::*;
+
+        match value {
+            JumpPastEnd => "jump-past-end",
+            JumpIntoInclude => "jump-into-include",
+            UnreachableRule => "unreachable-rule",
+            PermitSufficient => "permit-sufficient",
+            PasswordNullok => "password-nullok",
+            MissingDeny => "missing-deny",
+            DuplicateRule => "duplicate-rule",
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <&'static str>::from(*self).fmt(f)
+    }
+}
+
+impl Display for LintCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <&'static str>::from(*self).fmt(f)
+    }
+}
+
+impl Display for Lint<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use LintCode::*;
+
+        write!(f, "{}[{}]", self.severity(), self.code)?;
+        if let Some(rule) = self.rule {
+            write!(f, " {}", rule.span())?;
+        }
+
+        let message = match self.code {
+            JumpPastEnd => "jump points past the end of the stack",
+            JumpIntoInclude => "jump lands in the middle of an include",
+            UnreachableRule => "rule can never be reached",
+            PermitSufficient => "`pam_permit.so` is sufficient before other rules",
+            PasswordNullok => "password module allows empty passwords with `nullok`",
+            MissingDeny => "stack has no `pam_deny.so` fallback",
+            DuplicateRule => "rule is a duplicate of an earlier rule",
+        };
+        write!(f, ": {} in `{}` stack", message, self.domain)
+    }
+}
+
+impl PamConfig {
+    /// Lint the resolved stacks of all [`Domain`]s of a service
+    ///
+    /// See [`ResolvedStack::lint`] for the checks that are done.
+    pub fn lint(&self, service: &str) -> Result<Vec<Lint<'_>>, ResolveError> {
+        let domains = [
+            Domain::Auth,
+            Domain::Account,
+            Domain::Password,
+            Domain::Session,
+        ];
+
+        let mut lints = Vec::new();
+        for domain in domains {
+            lints.extend(self.resolve(service, domain)?.lint());
+        }
+
+        Ok(lints)
+    }
+}
+
+impl<'a> ResolvedStack<'a> {
+    /// Check this stack for common mistakes
+    ///
+    /// Substacks are checked as stacks on their own.
+    pub fn lint(&self) -> Vec<Lint<'a>> {
+        let mut lints = Vec::new();
+        self.lint_into(&mut lints);
+
+        // Linux-PAM fails an empty stack anyway, so it needs no fallback
+        if self.domain() == Domain::Auth && !self.entries().is_empty() && !self.has_deny() {
+            lints.push(Lint::new(
+                LintCode::MissingDeny,
+                self.domain(),
+                self.entries().last(),
+            ));
+        }
+
+        lints
+    }
+
+    fn lint_into(&self, lints: &mut Vec<Lint<'a>>) {
+        let domain = self.domain();
+        let entries = self.entries();
+        let reachable = reachable_entries(entries);
+        let jump_errors: Vec<(usize, JumpError)> = self
+            .level_jumps()
+            .into_iter()
+            .filter_map(|(i, jump)| jump.err().map(|error| (i, error)))
+            .collect();
+
+        for (i, entry) in entries.iter().enumerate() {
+            let rule = entry.rule();
+            let mut lint = |code| lints.push(Lint::new(code, domain, Some(entry)));
+
+            for (_, error) in jump_errors.iter().filter(|(from, _)| *from == i) {
+                lint(match error {
+                    JumpError::PastEnd { .. } => LintCode::JumpPastEnd,
+                    JumpError::IntoInclude { .. } => LintCode::JumpIntoInclude,
+                });
+            }
+
+            if !reachable[i] {
+                lint(LintCode::UnreachableRule);
+            }
+
+            if domain == Domain::Auth
+                && is_module(rule, PAM_PERMIT)
+                && matches!(rule.control(), Some(Control::Sufficient))
+                && i + 1 < entries.len()
+            {
+                lint(LintCode::PermitSufficient);
+            }
+
+            if domain == Domain::Password
+                && rule
+                    .module_arguments()
+                    .iter()
+                    .any(|argument| matches!(argument, ModuleArgument::Set(key) if key == "nullok"))
+            {
+                lint(LintCode::PasswordNullok);
+            }
+
+            let text = rule.to_string();
+            if entries[..i]
+                .iter()
+                .any(|earlier| earlier.rule().to_string() == text)
+            {
+                lint(LintCode::DuplicateRule);
+            }
+
+            if let StackEntry::Substack { stack, .. } = entry {
+                stack.lint_into(lints);
+            }
+        }
+    }
+
+    fn has_deny(&self) -> bool {
+        self.entries().iter().any(|entry| match entry {
+            StackEntry::Rule { rule, .. } => {
+                is_module(rule, PAM_DENY)
+                    && matches!(rule.control(), Some(Control::Required | Control::Requisite))
+            }
+            StackEntry::Substack { stack, .. } => stack.has_deny(),
+        })
+    }
+}
+
+/// Compute which entries can be reached by following the stack from the first entry
+fn reachable_entries(entries: &[StackEntry]) -> Vec<bool> {
+    let mut reachable = vec![false; entries.len() + 1];
+    reachable[0] = true;
+
+    for (i, entry) in entries.iter().enumerate() {
+        if !reachable[i] {
+            continue;
+        }
+
+        if !ends_stack(entry.rule()) {
+            reachable[i + 1] = true;
+        }
+
+        for n in jumps(entry.rule()) {
+            if let Some(target) = reachable.get_mut(i + n as usize + 1) {
+                *target = true;
+            }
+        }
+    }
+
+    reachable.truncate(entries.len());
+    reachable
+}
+
+/// Get whether a rule never continues with the next rule, whatever the module returns
+fn ends_stack(rule: &PamRule) -> bool {
+    match rule.control() {
+        Some(Control::Sufficient) => is_module(rule, PAM_PERMIT),
+        Some(Control::Requisite) => is_module(rule, PAM_DENY),
+        Some(control @ Control::Selection(_)) => control
+            .to_selection()
+            .items()
+            .iter()
+            .all(|item| matches!(item.action(), Action::Done | Action::Die)),
+        _ => false,
+    }
+}
+
+fn jumps(rule: &PamRule) -> impl Iterator<Item = u32> + '_ {
+    let items = match rule.control() {
+        Some(Control::Selection(selection)) => selection.items(),
+        _ => &[],
+    };
+
+    items.iter().filter_map(|item| match item.action() {
+        Action::JumpOver(n) => Some(n),
+        _ => None,
+    })
+}
+
+fn is_module(rule: &PamRule, name: &str) -> bool {
+    rule.module_path()
+        .is_some_and(|module_path| module_path.path().file_name() == Some(name.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn codes(pam_conf: &str, domain: Domain) -> Vec<(LintCode, usize)> {
+        let config = PamConfig::from_str(pam_conf).unwrap();
+        let stack = config.resolve("login", domain).unwrap();
+
+        stack
+            .lint()
+            .iter()
+            .map(|lint| (lint.code(), lint.origin().unwrap().index()))
+            .collect()
+    }
+
+    #[test]
+    fn clean() {
+        let pam_conf = r#"
+login   auth        required    pam_env.so
+login   auth        [success=1 default=ignore]  pam_unix.so nullok
+login   auth        requisite   pam_deny.so
+login   auth        required    pam_permit.so
+login   password    required    pam_unix.so sha512
+"#;
+        let config = PamConfig::from_str(pam_conf).unwrap();
+        assert!(config.lint("login").unwrap().is_empty());
+    }
+
+    #[test]
+    fn jumps_and_reachability() {
+        let pam_conf = r#"
+login   auth        [success=2 new_authtok_reqd=9 default=ignore]  pam_unix.so
+login   auth        [default=done]              pam_faildelay.so
+login   auth        required                    pam_env.so
+login   auth        requisite                   pam_deny.so
+"#;
+        assert_eq!(
+            codes(pam_conf, Domain::Auth),
+            [(LintCode::JumpPastEnd, 0), (LintCode::UnreachableRule, 2)]
+        );
+    }
+
+    #[test]
+    fn permit_and_deny() {
+        let pam_conf = r#"
+login   auth        sufficient  pam_permit.so
+login   auth        required    pam_unix.so
+"#;
+        assert_eq!(
+            codes(pam_conf, Domain::Auth),
+            [
+                (LintCode::PermitSufficient, 0),
+                (LintCode::UnreachableRule, 1),
+                (LintCode::MissingDeny, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn nullok_and_duplicates() {
+        let pam_conf = r#"
+login   password    required    pam_unix.so nullok
+login   password    required    pam_unix.so nullok
+"#;
+        assert_eq!(
+            codes(pam_conf, Domain::Password),
+            [
+                (LintCode::PasswordNullok, 0),
+                (LintCode::PasswordNullok, 1),
+                (LintCode::DuplicateRule, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_stack() {
+        let config = PamConfig::from_str("cron account required pam_unix.so\n").unwrap();
+        assert!(config.lint("cron").unwrap().is_empty());
+    }
+
+    #[test]
+    fn display() {
+        let config = PamConfig::from_str("login auth required pam_unix.so\n").unwrap();
+        let lints = config.lint("login").unwrap();
+
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].severity(), Severity::Warning);
+        assert_eq!(
+            lints[0].to_string(),
+            "warning[missing-deny] 1:1: stack has no `pam_deny.so` fallback in `auth` stack"
+        );
+    }
+}
@@ -1,32 +1,96 @@
+use std::borrow::Cow;
 use std::fmt::Display;
 use std::str::FromStr;
 
-#[derive(Debug)]
+use crate::parsing::escape_string;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ModuleArgument {
     KeyValue { key: String, value: String },
     Set(String),
 }
 
-impl FromStr for ModuleArgument {
-    type Err = ();
+/// Borrowed variant of a [`ModuleArgument`] that points into the source it was parsed from
+///
+/// The key and value are only owned if an escaped `]` had to be removed from the argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleArgumentRef<'a> {
+    KeyValue {
+        key: Cow<'a, str>,
+        value: Cow<'a, str>,
+    },
+    Set(Cow<'a, str>),
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl<'a> ModuleArgumentRef<'a> {
+    /// Parse a single argument that was already taken from a line
+    ///
+    /// An invalid argument is given back as the error.
+    pub(crate) fn parse(s: Cow<'a, str>) -> Result<Self, Cow<'a, str>> {
         let Some(equals_position) = s.find('=') else {
-            if s.contains(|c| c == ' ' || c == '\t' || c == '\n') {
-                return Err(());
+            if s.contains([' ', '\t', '\n']) {
+                return Err(s);
             }
 
-            return Ok(ModuleArgument::Set(String::from(s)));
+            return Ok(ModuleArgumentRef::Set(s));
         };
 
         if equals_position == 0 {
-            return Err(());
+            return Err(s);
         }
 
-        let key = s[..equals_position].to_string();
-        let value = s[equals_position + 1..].to_string();
+        let (key, value) = match s {
+            Cow::Borrowed(s) => (
+                Cow::Borrowed(&s[..equals_position]),
+                Cow::Borrowed(&s[equals_position + 1..]),
+            ),
+            Cow::Owned(mut key) => {
+                let value = key.split_off(equals_position + 1);
+                key.truncate(equals_position);
+                (Cow::Owned(key), Cow::Owned(value))
+            }
+        };
 
-        Ok(ModuleArgument::KeyValue { key, value })
+        Ok(ModuleArgumentRef::KeyValue { key, value })
+    }
+
+    /// Convert into a [`ModuleArgument`], which only allocates for borrowed parts
+    pub fn into_owned(self) -> ModuleArgument {
+        match self {
+            ModuleArgumentRef::KeyValue { key, value } => ModuleArgument::KeyValue {
+                key: key.into_owned(),
+                value: value.into_owned(),
+            },
+            ModuleArgumentRef::Set(key) => ModuleArgument::Set(key.into_owned()),
+        }
+    }
+
+    /// Convert all borrowed parts into owned ones, so that the argument no longer borrows from
+    /// the source
+    pub fn into_static(self) -> ModuleArgumentRef<'static> {
+        match self {
+            ModuleArgumentRef::KeyValue { key, value } => ModuleArgumentRef::KeyValue {
+                key: Cow::Owned(key.into_owned()),
+                value: Cow::Owned(value.into_owned()),
+            },
+            ModuleArgumentRef::Set(key) => ModuleArgumentRef::Set(Cow::Owned(key.into_owned())),
+        }
+    }
+}
+
+impl From<ModuleArgumentRef<'_>> for ModuleArgument {
+    fn from(value: ModuleArgumentRef<'_>) -> Self {
+        value.into_owned()
+    }
+}
+
+impl FromStr for ModuleArgument {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ModuleArgumentRef::parse(Cow::Borrowed(s))
+            .map(ModuleArgument::from)
+            .map_err(|_| ())
     }
 }
 
@@ -35,14 +99,19 @@ impl Display for ModuleArgument {
         use ModuleArgument::*;
 
         match self {
-            KeyValue { key, value } => {
-                if value.contains(|c| c == ' ' || c == '\t') {
-                    write!(f, "[{}={}]", key, value)
-                } else {
-                    write!(f, "{}={}", key, value)
-                }
-            }
-            Set(key) => key.fmt(f),
+            KeyValue { key, value } => escape_string(&format!("{}={}", key, value)).fmt(f),
+            Set(key) => escape_string(key).fmt(f),
+        }
+    }
+}
+
+impl Display for ModuleArgumentRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ModuleArgumentRef::*;
+
+        match self {
+            KeyValue { key, value } => escape_string(&format!("{}={}", key, value)).fmt(f),
+            Set(key) => escape_string(key).fmt(f),
         }
     }
 }
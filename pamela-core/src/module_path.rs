@@ -1,19 +1,191 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::fmt::Display;
 
-#[derive(Debug)]
+/// Placeholder in module paths that Linux-PAM replaces with the instruction set directory
+const ISA_PLACEHOLDER: &str = "$ISA";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ModulePathType {
     Absolute,
     Relative,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ModulePath {
     path_type: ModulePathType,
     path: PathBuf,
 }
 
+/// Set of directories that relative [`ModulePath`]s are looked up in
+///
+/// The [`Default`] contains the module directories used by the common distributions:
+/// `/lib/security`, `/lib64/security`, `/usr/lib/security`, `/usr/lib64/security` and the
+/// multiarch directories such as `/usr/lib/x86_64-linux-gnu/security`. The multiarch directories
+/// are left out on targets without a Debian multiarch triplet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleSearchPath {
+    dirs: Vec<PathBuf>,
+    isa: String,
+}
+
+/// Where a [`ModulePath`] was found on disk by [`ModulePath::resolve`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleLocation {
+    /// The module is a regular file at this path
+    Found(PathBuf),
+    /// Something exists at this path, but it is not a regular file
+    NotAFile(PathBuf),
+    /// The module does not exist at any of these paths
+    Missing(Vec<PathBuf>),
+}
+
+impl ModulePath {
+    /// Create a [`ModulePath`] without checking that `path` is not empty
+    pub(crate) fn new_unchecked(path: String) -> Self {
+        let path_type = match path.starts_with('/') {
+            true => ModulePathType::Absolute,
+            false => ModulePathType::Relative,
+        };
+
+        let path = PathBuf::from(path);
+
+        ModulePath { path_type, path }
+    }
+
+    /// Get the path as written in the configuration
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get whether the path is absolute and therefore not looked up in the module directories
+    pub fn is_absolute(&self) -> bool {
+        matches!(self.path_type, ModulePathType::Absolute)
+    }
+
+    /// Find the module on disk
+    ///
+    /// An absolute path is used as is, a relative path is looked up in every directory of the
+    /// `search_path` in order. In both cases, `$ISA` is replaced in the same way as Linux-PAM
+    /// does. The first path that exists is used.
+    pub fn resolve(&self, search_path: &ModuleSearchPath) -> ModuleLocation {
+        let candidates: Vec<PathBuf> = match self.path_type {
+            ModulePathType::Absolute => vec![search_path.substitute_isa(&self.path)],
+            ModulePathType::Relative => search_path
+                .dirs
+                .iter()
+                .map(|dir| search_path.substitute_isa(&dir.join(&self.path)))
+                .collect(),
+        };
+
+        for candidate in &candidates {
+            // NOTE: This follows symbolic links, in the same way as `dlopen` does.
+            let Ok(metadata) = candidate.metadata() else {
+                continue;
+            };
+
+            return match metadata.is_file() {
+                true => ModuleLocation::Found(candidate.clone()),
+                false => ModuleLocation::NotAFile(candidate.clone()),
+            };
+        }
+
+        ModuleLocation::Missing(candidates)
+    }
+}
+
+impl ModuleSearchPath {
+    /// Create a [`ModuleSearchPath`] from directories ordered from the highest to the lowest
+    /// priority, and the directory that replaces `$ISA`
+    pub fn new(
+        dirs: impl IntoIterator<Item = impl Into<PathBuf>>,
+        isa: impl Into<String>,
+    ) -> Self {
+        Self {
+            dirs: dirs.into_iter().map(Into::into).collect(),
+            isa: isa.into(),
+        }
+    }
+
+    /// Get the directories ordered from the highest to the lowest priority
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Get the directory that replaces `$ISA`
+    ///
+    /// This is relative to the directory that contains `$ISA`, e.g. `../../lib64/security`.
+    pub fn isa(&self) -> &str {
+        &self.isa
+    }
+
+    fn substitute_isa(&self, path: &Path) -> PathBuf {
+        match path.to_str() {
+            Some(s) if s.contains(ISA_PLACEHOLDER) => {
+                PathBuf::from(s.replace(ISA_PLACEHOLDER, &self.isa))
+            }
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+impl Default for ModuleSearchPath {
+    fn default() -> Self {
+        let mut dirs = vec![
+            PathBuf::from("/lib/security"),
+            PathBuf::from("/lib64/security"),
+            PathBuf::from("/usr/lib/security"),
+            PathBuf::from("/usr/lib64/security"),
+        ];
+        if let Some(multiarch) = multiarch() {
+            dirs.push(Path::new("/lib").join(multiarch).join("security"));
+            dirs.push(Path::new("/usr/lib").join(multiarch).join("security"));
+        }
+
+        // Linux-PAM uses the name of its own library directory by default
+        let isa = match cfg!(target_pointer_width = "64") {
+            true => "../../lib64/security",
+            false => "../../lib/security",
+        };
+
+        Self::new(dirs, isa)
+    }
+}
+
+/// Get the Debian multiarch triplet of the target
+///
+/// The triplet does not follow from the Rust target name, e.g. `x86` is `i386-linux-gnu` and a
+/// little-endian `powerpc64` is `powerpc64le-linux-gnu`.
+fn multiarch() -> Option<&'static str> {
+    if !cfg!(all(target_os = "linux", target_env = "gnu")) {
+        return None;
+    }
+
+    let is_little_endian = cfg!(target_endian = "little");
+    let triplet = match std::env::consts::ARCH {
+        "x86_64" if cfg!(target_pointer_width = "32") => "x86_64-linux-gnux32",
+        "x86_64" => "x86_64-linux-gnu",
+        "x86" => "i386-linux-gnu",
+        "aarch64" => "aarch64-linux-gnu",
+        "arm" if cfg!(target_abi = "eabihf") => "arm-linux-gnueabihf",
+        "arm" => "arm-linux-gnueabi",
+        "powerpc64" if is_little_endian => "powerpc64le-linux-gnu",
+        "powerpc64" => "powerpc64-linux-gnu",
+        "powerpc" => "powerpc-linux-gnu",
+        "mips64" if is_little_endian => "mips64el-linux-gnuabi64",
+        "mips64" => "mips64-linux-gnuabi64",
+        "mips" if is_little_endian => "mipsel-linux-gnu",
+        "mips" => "mips-linux-gnu",
+        "riscv64" => "riscv64-linux-gnu",
+        "s390x" => "s390x-linux-gnu",
+        "sparc64" => "sparc64-linux-gnu",
+        "loongarch64" => "loongarch64-linux-gnu",
+        _ => return None,
+    };
+
+    Some(triplet)
+}
+
 impl FromStr for ModulePath {
     type Err = ();
 
@@ -22,14 +194,7 @@ impl FromStr for ModulePath {
             return Err(());
         }
 
-        let path_type = match s.starts_with('/') {
-            true => ModulePathType::Absolute,
-            false => ModulePathType::Relative,
-        };
-
-        let path = PathBuf::from(s);
-
-        Ok(ModulePath { path_type, path })
+        Ok(ModulePath::new_unchecked(s.to_string()))
     }
 }
 
@@ -38,3 +203,64 @@ impl Display for ModulePath {
         write!(f, "{}", self.path.display())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn resolve() {
+        let root = std::env::temp_dir().join(format!("pamela-modules-{}", std::process::id()));
+        let lib = root.join("lib/security");
+        let lib64 = root.join("lib64/security");
+        fs::create_dir_all(&lib).unwrap();
+        fs::create_dir_all(&lib64).unwrap();
+        fs::create_dir_all(lib.join("pam_dir.so")).unwrap();
+        fs::write(lib.join("pam_unix.so"), "").unwrap();
+        fs::write(lib64.join("pam_unix.so"), "").unwrap();
+        fs::write(lib64.join("pam_env.so"), "").unwrap();
+
+        let search_path = ModuleSearchPath::new([&lib, &lib64], "../../lib64/security");
+        let resolve = |s: &str| ModulePath::from_str(s).unwrap().resolve(&search_path);
+
+        let pam_unix = resolve("pam_unix.so");
+        let pam_env = resolve("pam_env.so");
+        let pam_dir = resolve("pam_dir.so");
+        let pam_missing = resolve("pam_missing.so");
+        let absolute = resolve(&format!("{}/$ISA/pam_env.so", lib.display()));
+
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(pam_unix, ModuleLocation::Found(lib.join("pam_unix.so")));
+        assert_eq!(pam_env, ModuleLocation::Found(lib64.join("pam_env.so")));
+        assert_eq!(pam_dir, ModuleLocation::NotAFile(lib.join("pam_dir.so")));
+        assert_eq!(
+            pam_missing,
+            ModuleLocation::Missing(vec![
+                lib.join("pam_missing.so"),
+                lib64.join("pam_missing.so")
+            ])
+        );
+        assert_eq!(
+            absolute,
+            ModuleLocation::Found(lib.join("../../lib64/security/pam_env.so"))
+        );
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux", target_env = "gnu"))]
+    fn default_multiarch() {
+        let search_path = ModuleSearchPath::default();
+        let triplet = match cfg!(target_pointer_width = "64") {
+            true => "x86_64-linux-gnu",
+            false => "x86_64-linux-gnux32",
+        };
+
+        assert_eq!(multiarch(), Some(triplet));
+        assert!(search_path
+            .dirs()
+            .contains(&Path::new("/usr/lib").join(triplet).join("security")));
+    }
+}
@@ -0,0 +1,596 @@
+//! Parsing of the OpenPAM policy syntax
+//!
+//! OpenPAM, the PAM implementation of FreeBSD, NetBSD and macOS, reads policies that look like
+//! the Linux-PAM configuration, but differ in the details:
+//!
+//! - There is an additional `binding` control, see [`Control::Binding`].
+//! - Other services are only included with `include`. There is no `substack`, `@include`, `-`
+//!   prefix or bracketed selection.
+//! - Words are split in the same way as a shell does. `'` and `"` quote, a backslash escapes the
+//!   next character, and a `#` only starts a comment at the start of a word.
+//! - Service names consist of the POSIX portable filename characters `A-Z`, `a-z`, `0-9`, `.`,
+//!   `_` and `-`. Module names may contain `/` as well.
+//! - The policy of a service comes from the first location on the policy path that has one,
+//!   see [`PamConfig::from_openpam_system`].
+//!
+//! The rules are parsed into the same [`PamRule`]s as the Linux-PAM syntax.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{
+    Control, ControlParseError, Domain, InclusionMethod, ModuleArgument, ModulePath, PamConfig,
+    PamConfigError, PamConfigSyntaxError, PamConfigSyntaxErrorKind, PamRule, PamService,
+    RuleContent, Span,
+};
+
+/// Locations that OpenPAM searches for the policy of a service, in order
+const OPENPAM_POLICY_PATH: [&str; 4] = [
+    "/etc/pam.d",
+    "/etc/pam.conf",
+    "/usr/local/etc/pam.d",
+    "/usr/local/etc/pam.conf",
+];
+
+/// Iterator over the packed OpenPAM policy syntax, created with
+/// [`PamRule::openpam_packed_iter`]
+///
+/// Every rule starts with the name of its service, e.g. `login auth required pam_unix.so`.
+pub struct OpenPamPackedRuleIterator<'a>(PolicyLines<'a>);
+
+/// Iterator over the separated OpenPAM policy syntax of a single service, created with
+/// [`PamRule::openpam_separated_iter`]
+pub struct OpenPamSeparatedRuleIterator<'a>(PolicyLines<'a>);
+
+/// Word of a policy with the quotes and escapes removed
+struct Word {
+    text: String,
+    range: Range<usize>,
+}
+
+/// Splitter of a policy into lines of words in the same way as `openpam_readlinev`
+struct PolicyLines<'a> {
+    source: &'a str,
+    path: Option<Arc<Path>>,
+    offset: usize,
+}
+
+impl PamRule {
+    /// Create an iterator over rules in the packed OpenPAM policy syntax
+    ///
+    /// This is the OpenPAM counterpart of [`PamRule::packed_iter`].
+    pub fn openpam_packed_iter(s: &str) -> OpenPamPackedRuleIterator<'_> {
+        OpenPamPackedRuleIterator(PolicyLines::new(s, None))
+    }
+
+    /// Create an iterator over rules in the separated OpenPAM policy syntax
+    ///
+    /// This is the OpenPAM counterpart of [`PamRule::separated_iter`].
+    pub fn openpam_separated_iter(s: &str) -> OpenPamSeparatedRuleIterator<'_> {
+        OpenPamSeparatedRuleIterator(PolicyLines::new(s, None))
+    }
+}
+
+impl PamService {
+    /// Parse a [`PamService`] named `name` from a [`&str`] in the separated OpenPAM policy
+    /// syntax
+    pub fn from_openpam_str(name: &str, s: &str) -> Result<PamService, PamConfigSyntaxError> {
+        let rules = PamRule::openpam_separated_iter(s)
+            .collect::<Result<Vec<PamRule>, PamConfigSyntaxError>>()?;
+        Ok(PamService::new(name, rules))
+    }
+
+    /// Read a [`PamService`] from an OpenPAM policy file
+    ///
+    /// The file name is used as the service name, which has to be a valid OpenPAM service name.
+    pub fn from_openpam_file(path: impl AsRef<Path>) -> Result<PamService, PamConfigError> {
+        let path = path.as_ref();
+        let (name, contents) = Self::read_file(path)?;
+
+        let lines = PolicyLines::new(&contents, Some(Arc::from(path)));
+        if !is_valid_service_name(&name) {
+            let kind = PamConfigSyntaxErrorKind::WrongServiceName(name);
+            return Err(lines.error(0..0, kind).into());
+        }
+
+        let rules = OpenPamSeparatedRuleIterator(lines)
+            .collect::<Result<Vec<PamRule>, PamConfigSyntaxError>>()?;
+
+        Ok(Self {
+            name,
+            layer: path.parent().map(Path::to_path_buf),
+            rules,
+        })
+    }
+}
+
+impl PamConfig {
+    /// Parse a [`PamConfig`] from a [`&str`] in the packed OpenPAM policy syntax
+    pub fn from_openpam_str(s: &str) -> Result<PamConfig, PamConfigSyntaxError> {
+        let packed_rules = PamRule::openpam_packed_iter(s)
+            .collect::<Result<Vec<(String, PamRule)>, PamConfigSyntaxError>>()?;
+        Ok(Self::from_packed_rules(packed_rules))
+    }
+
+    /// Read a [`PamConfig`] from a packed OpenPAM policy file, such as `/etc/pam.conf`
+    pub fn from_openpam_file(path: impl AsRef<Path>) -> Result<PamConfig, PamConfigError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+
+        let packed_rules =
+            OpenPamPackedRuleIterator(PolicyLines::new(&contents, Some(Arc::from(path))))
+                .collect::<Result<Vec<(String, PamRule)>, PamConfigSyntaxError>>()?;
+
+        Ok(Self::from_packed_rules(packed_rules))
+    }
+
+    /// Read a [`PamConfig`] from an OpenPAM policy path
+    ///
+    /// Every location is either a directory with a policy file per service or a packed policy
+    /// file. In the same way as OpenPAM, a service uses the policy from the first location that
+    /// has one and the later locations are not consulted for it. Locations that do not exist
+    /// are skipped, and so are files in a directory that are not valid service names, since
+    /// OpenPAM never looks them up.
+    pub fn from_openpam_policy_path(
+        policy_path: impl IntoIterator<Item = impl AsRef<Path>>,
+    ) -> Result<PamConfig, PamConfigError> {
+        let mut services = BTreeMap::new();
+
+        for location in policy_path {
+            let location = location.as_ref();
+
+            if location.is_dir() {
+                for dir_entry in fs::read_dir(location)? {
+                    let path = dir_entry?.path();
+                    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                        continue;
+                    };
+                    if path.is_dir() || !is_valid_service_name(name) || services.contains_key(name)
+                    {
+                        continue;
+                    }
+
+                    let service = PamService::from_openpam_file(&path)?;
+                    services.insert(service.name.clone(), service);
+                }
+            } else if location.exists() {
+                for service in PamConfig::from_openpam_file(location)?.services {
+                    services.entry(service.name.clone()).or_insert(service);
+                }
+            }
+        }
+
+        Ok(PamConfig {
+            services: services.into_values().collect(),
+        })
+    }
+
+    /// Read a [`PamConfig`] from the OpenPAM policy path of the current system
+    ///
+    /// OpenPAM searches `/etc/pam.d`, `/etc/pam.conf`, `/usr/local/etc/pam.d` and
+    /// `/usr/local/etc/pam.conf` in that order. See [`PamConfig::from_openpam_policy_path`] for
+    /// the details.
+    pub fn from_openpam_system() -> Result<PamConfig, PamConfigError> {
+        Self::from_openpam_policy_path(OPENPAM_POLICY_PATH)
+    }
+}
+
+impl Iterator for OpenPamPackedRuleIterator<'_> {
+    type Item = Result<(String, PamRule), PamConfigSyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let words = match self.0.next_line()? {
+            Ok(words) => words,
+            Err(err) => return Some(Err(err)),
+        };
+
+        // A line is never empty, so there is always a service name
+        let (service, words) = words.split_first()?;
+        if !is_valid_service_name(&service.text) {
+            let kind = PamConfigSyntaxErrorKind::WrongServiceName(service.text.clone());
+            return Some(Err(self.0.error(service.range.clone(), kind)));
+        }
+
+        let rule = take_rule(&self.0, service.range.start, words);
+        Some(rule.map(|rule| (service.text.clone(), rule)))
+    }
+}
+
+impl Iterator for OpenPamSeparatedRuleIterator<'_> {
+    type Item = Result<PamRule, PamConfigSyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let words = match self.0.next_line()? {
+            Ok(words) => words,
+            Err(err) => return Some(Err(err)),
+        };
+
+        Some(take_rule(&self.0, words[0].range.start, &words))
+    }
+}
+
+/// Parse the words of a rule after the service name, where `start` is where the rule starts
+fn take_rule(
+    lines: &PolicyLines,
+    start: usize,
+    words: &[Word],
+) -> Result<PamRule, PamConfigSyntaxError> {
+    use PamConfigSyntaxErrorKind::*;
+
+    let end = words.last().map_or(start, |word| word.range.end);
+    let missing = |kind| lines.error(end..end, kind);
+
+    let (domain, words) = words
+        .split_first()
+        .ok_or_else(|| missing(WrongDomain(String::new())))?;
+    let domain = domain
+        .text
+        .parse::<Domain>()
+        .map_err(|_| lines.error(domain.range.clone(), WrongDomain(domain.text.clone())))?;
+
+    let (control, words) = words
+        .split_first()
+        .ok_or_else(|| missing(WrongControl(ControlParseError::EmptyString)))?;
+
+    let content = if control.text == "include" {
+        let (service, words) = words
+            .split_first()
+            .ok_or_else(|| missing(WrongServiceName(String::new())))?;
+        if !is_valid_service_name(&service.text) {
+            return Err(lines.error(
+                service.range.clone(),
+                WrongServiceName(service.text.clone()),
+            ));
+        }
+
+        // OpenPAM rejects anything after the included service
+        if let (Some(first), Some(last)) = (words.first(), words.last()) {
+            let garbage = words.iter().map(|word| word.text.as_str());
+            let kind = WrongModuleArgs(garbage.collect::<Vec<_>>().join(" "));
+            return Err(lines.error(first.range.start..last.range.end, kind));
+        }
+
+        RuleContent::ServiceInclusion {
+            method: InclusionMethod::Include,
+            service: service.text.clone(),
+        }
+    } else {
+        let control_range = control.range.clone();
+        let control = match control.text.as_str() {
+            "required" => Control::Required,
+            "requisite" => Control::Requisite,
+            "sufficient" => Control::Sufficient,
+            "optional" => Control::Optional,
+            "binding" => Control::Binding,
+            other => {
+                let kind = WrongControl(ControlParseError::UnknownPreset(other.to_string()));
+                return Err(lines.error(control_range, kind));
+            }
+        };
+
+        let (module_path, words) = words
+            .split_first()
+            .ok_or_else(|| missing(WrongModulePath(String::new())))?;
+        if !is_valid_module_name(&module_path.text) {
+            let kind = WrongModulePath(module_path.text.clone());
+            return Err(lines.error(module_path.range.clone(), kind));
+        }
+
+        let module_arguments = words
+            .iter()
+            .map(|word| match word.text.find('=') {
+                Some(position) if position > 0 => ModuleArgument::KeyValue {
+                    key: word.text[..position].to_string(),
+                    value: word.text[position + 1..].to_string(),
+                },
+                _ => ModuleArgument::Set(word.text.clone()),
+            })
+            .collect();
+
+        RuleContent::Entry {
+            control,
+            module_path: ModulePath::new_unchecked(module_path.text.clone()),
+            module_arguments,
+        }
+    };
+
+    Ok(PamRule {
+        span: lines.span(start..end),
+        is_logging_enabled: true,
+        domain: Some(domain),
+        content,
+    })
+}
+
+/// Get whether a character is in the POSIX portable filename character set
+fn is_portable_filename_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')
+}
+
+/// Get whether OpenPAM accepts `name` as a service name
+fn is_valid_service_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_portable_filename_char)
+}
+
+/// Get whether OpenPAM accepts `name` as a module name
+fn is_valid_module_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| is_portable_filename_char(c) || c == '/')
+}
+
+impl<'a> PolicyLines<'a> {
+    fn new(source: &'a str, path: Option<Arc<Path>>) -> Self {
+        Self {
+            source,
+            path,
+            offset: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+
+    /// Take the words of the next line that has any
+    ///
+    /// Returns `None` at the end of the source.
+    fn next_line(&mut self) -> Option<Result<Vec<Word>, PamConfigSyntaxError>> {
+        loop {
+            let mut words = Vec::new();
+
+            loop {
+                let rest = &self.source[self.offset..];
+                let trimmed = rest.trim_start_matches([' ', '\t']);
+                self.offset += rest.len() - trimmed.len();
+
+                if trimmed.starts_with("\\\n") {
+                    // A line continuation between words
+                    self.offset += 2;
+                } else if trimmed.starts_with('\n') {
+                    self.offset += 1;
+                    break;
+                } else if trimmed.starts_with('#') {
+                    // The comment ends before the new line, which still ends the line
+                    self.offset += trimmed.find('\n').unwrap_or(trimmed.len());
+                } else if trimmed.is_empty() {
+                    break;
+                } else {
+                    match self.take_word() {
+                        Ok(word) => words.push(word),
+                        Err(err) => return Some(Err(err)),
+                    }
+                }
+            }
+
+            if !words.is_empty() {
+                return Some(Ok(words));
+            }
+            if self.offset >= self.source.len() {
+                return None;
+            }
+        }
+    }
+
+    /// Take a word in the same way as `openpam_readword`
+    fn take_word(&mut self) -> Result<Word, PamConfigSyntaxError> {
+        let start = self.offset;
+        let mut text = String::new();
+        let mut quote = None;
+        let mut is_escaped = false;
+
+        while let Some(c) = self.peek() {
+            if quote.is_none() && !is_escaped && matches!(c, ' ' | '\t' | '\n') {
+                break;
+            }
+            self.offset += c.len_utf8();
+
+            if c == '\\' && !is_escaped && quote != Some('\'') {
+                is_escaped = true;
+            } else if matches!(c, '\'' | '"') && quote.is_none() && !is_escaped {
+                quote = Some(c);
+            } else if Some(c) == quote && !is_escaped {
+                quote = None;
+            } else if c == '\n' && is_escaped {
+                // A line continuation within a word
+                is_escaped = false;
+            } else {
+                // Within double quotes, only a backslash and the quote itself are escaped
+                if is_escaped && quote.is_some() && c != '\\' && Some(c) != quote {
+                    text.push('\\');
+                }
+                text.push(c);
+                is_escaped = false;
+            }
+        }
+
+        if quote.is_some() {
+            let end = self.source.len();
+            return Err(self.error(start..end, PamConfigSyntaxErrorKind::UnclosedQuote));
+        }
+
+        Ok(Word {
+            text,
+            range: start..self.offset,
+        })
+    }
+
+    /// Create a [`Span`] from a byte range into the source
+    fn span(&self, range: Range<usize>) -> Span {
+        let before = &self.source[..range.start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() + 1;
+
+        Span::new(self.path.clone(), range, line, column)
+    }
+
+    fn error(&self, range: Range<usize>, kind: PamConfigSyntaxErrorKind) -> PamConfigSyntaxError {
+        PamConfigSyntaxError::new(kind, self.span(range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy() {
+        let source = "# $FreeBSD$\n\
+                      auth\t\tsufficient\tpam_opie.so\t\tno_warn no_fake_prompts\n\
+                      auth\t\trequisite\tpam_opieaccess.so\tno_warn allow_local\n\
+                      auth\t\tbinding\t\tpam_unix.so\t\tno_warn try_first_pass \\\n\
+                      \t\t\tprompt=\"Pass word: \" dir='/tmp/a b'\n\
+                      account\t\tinclude\t\tsystem # trailing comment\n";
+        let service = PamService::from_openpam_str("login", source).unwrap();
+        let rules = service.rules();
+
+        assert_eq!(rules.len(), 4);
+        assert!(matches!(rules[2].control(), Some(Control::Binding)));
+        assert_eq!(rules[2].span().line(), 4);
+        assert!(matches!(
+            &rules[2].module_arguments()[2],
+            ModuleArgument::KeyValue { key, value } if key == "prompt" && value == "Pass word: "
+        ));
+        assert!(matches!(
+            &rules[2].module_arguments()[3],
+            ModuleArgument::KeyValue { key, value } if key == "dir" && value == "/tmp/a b"
+        ));
+        assert_eq!(rules[3].included_service(), Some("system"));
+        assert_eq!(rules[3].domain(), Some(Domain::Account));
+
+        let config = PamConfig::from_openpam_str("sshd auth include system\n").unwrap();
+        assert_eq!(config.services()[0].name(), "sshd");
+
+        // `binding` is not a Linux-PAM control
+        assert!(PamService::from_str("auth binding pam_unix.so\n").is_err());
+    }
+
+    #[test]
+    fn words() {
+        let words = |source: &str| -> Vec<String> {
+            let rule = PamRule::openpam_separated_iter(source)
+                .next()
+                .unwrap()
+                .unwrap();
+            rule.module_arguments()
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+
+        assert_eq!(
+            words("auth required pam_x.so a#b c\\ d\n"),
+            ["a#b", "[c d]"]
+        );
+        assert_eq!(
+            words("auth required pam_x.so \"a\\b\\\"c\" 'a\\b'\n"),
+            ["a\\b\"c", "a\\b"]
+        );
+        assert_eq!(words("auth required pam_x.so a\\\nb\n"), ["ab"]);
+    }
+
+    #[test]
+    fn errors() {
+        use PamConfigSyntaxErrorKind::*;
+
+        let error = |source: &str| {
+            PamRule::openpam_packed_iter(source)
+                .find_map(Result::err)
+                .unwrap()
+        };
+
+        for (source, expected) in [
+            (
+                "login auth [success=ok] pam_unix.so\n",
+                "1:12: unknown control `[success=ok]`",
+            ),
+            (
+                "login auth substack system\n",
+                "1:12: unknown control `substack`",
+            ),
+            (
+                "login -auth required pam_unix.so\n",
+                "1:7: unknown domain `-auth`",
+            ),
+            (
+                "log/in auth required pam_unix.so\n",
+                "1:1: invalid service name `log/in`",
+            ),
+            (
+                "login auth include ../system\n",
+                "1:20: invalid service name `../system`",
+            ),
+            (
+                "login auth include system x\n",
+                "1:27: invalid module argument `x`",
+            ),
+            (
+                "login auth required pam_$ISA.so\n",
+                "1:21: invalid module path `pam_$ISA.so`",
+            ),
+            ("login auth required\n", "1:20: invalid module path ``"),
+        ] {
+            assert_eq!(error(source).to_string(), expected, "{source}");
+        }
+
+        let unclosed = error("login auth required pam_unix.so 'a\n");
+        assert!(matches!(unclosed.kind(), UnclosedQuote));
+
+        // A rule after an error is still parsed
+        let rules: Vec<_> =
+            PamRule::openpam_separated_iter("auth x pam_unix.so\nauth required y\n").collect();
+        assert!(rules[0].is_err() && rules[1].is_ok());
+    }
+
+    #[test]
+    fn policy_path() {
+        let root = std::env::temp_dir().join(format!("pamela-openpam-{}", std::process::id()));
+        let etc = root.join("etc/pam.d");
+        let local = root.join("usr/local/etc/pam.d");
+        fs::create_dir_all(&etc).unwrap();
+        fs::create_dir_all(&local).unwrap();
+
+        fs::write(etc.join("login"), "auth required pam_unix.so\n").unwrap();
+        fs::write(etc.join("README.md~"), "not a policy\n").unwrap();
+        fs::write(
+            root.join("etc/pam.conf"),
+            "login auth required pam_deny.so\nsshd auth required pam_unix.so\n",
+        )
+        .unwrap();
+        fs::write(local.join("sshd"), "auth required pam_deny.so\n").unwrap();
+        fs::write(local.join("sudo"), "auth binding pam_unix.so\n").unwrap();
+
+        let config = PamConfig::from_openpam_policy_path([
+            etc.clone(),
+            root.join("etc/pam.conf"),
+            local.clone(),
+            root.join("usr/local/etc/pam.conf"),
+        ]);
+
+        fs::remove_dir_all(&root).unwrap();
+        let config = config.unwrap();
+
+        let services: Vec<(&str, Option<&Path>, String)> = config
+            .services()
+            .iter()
+            .map(|service| {
+                let module = service.rules()[0].module_path().unwrap().to_string();
+                (service.name(), service.layer(), module)
+            })
+            .collect();
+
+        assert_eq!(
+            services,
+            [
+                ("login", Some(etc.as_path()), "pam_unix.so".to_string()),
+                ("sshd", None, "pam_unix.so".to_string()),
+                ("sudo", Some(local.as_path()), "pam_unix.so".to_string()),
+            ]
+        );
+    }
+}
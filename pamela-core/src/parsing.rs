@@ -1,4 +1,132 @@
 use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::span::Span;
+use crate::{PamConfigSyntaxError, PamConfigSyntaxErrorKind};
+
+/// Logical line of a configuration source with all line continuations resolved
+pub(crate) struct EscapedLine<'a> {
+    pub(crate) text: Cow<'a, str>,
+    raw: &'a str,
+    start: usize,
+    line: usize,
+    removed: Vec<usize>,
+    path: Option<Arc<Path>>,
+}
+
+/// Iterator over the [`EscapedLine`]s of a source that skips empty and comment lines
+pub(crate) struct EscapedLines<'a> {
+    source: &'a str,
+    base: usize,
+    offset: usize,
+    line: usize,
+    path: Option<Arc<Path>>,
+}
+
+impl<'a> EscapedLine<'a> {
+    /// Get the byte offset of a suffix of [`EscapedLine::text`]
+    pub(crate) fn offset_of(&self, rest: &str) -> usize {
+        self.text.len() - rest.len()
+    }
+
+    /// Create a [`Span`] from a byte range into [`EscapedLine::text`]
+    pub(crate) fn span(&self, range: Range<usize>) -> Span {
+        let start = self.raw_offset(range.start);
+        let end = if range.end > range.start {
+            self.raw_offset(range.end - 1) + 1
+        } else {
+            start
+        };
+
+        let before = &self.raw[..start];
+        let line = self.line + before.matches('\n').count();
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() + 1;
+
+        Span::new(
+            self.path.clone(),
+            self.start + start..self.start + end,
+            line,
+            column,
+        )
+    }
+
+    /// Create a [`PamConfigSyntaxError`] from a byte range into [`EscapedLine::text`]
+    pub(crate) fn error(
+        &self,
+        range: Range<usize>,
+        kind: PamConfigSyntaxErrorKind,
+    ) -> PamConfigSyntaxError {
+        PamConfigSyntaxError::new(kind, self.span(range))
+    }
+
+    fn raw_offset(&self, offset: usize) -> usize {
+        let mut raw_offset = offset;
+        for &removed in &self.removed {
+            if removed > raw_offset {
+                break;
+            }
+            raw_offset += 1;
+        }
+        raw_offset.min(self.raw.len())
+    }
+}
+
+impl<'a> EscapedLines<'a> {
+    pub(crate) fn new(source: &'a str, path: Option<Arc<Path>>) -> Self {
+        Self::at(source, path, 0, 1)
+    }
+
+    /// Create an iterator over a part of a larger source that starts at byte offset `base` and
+    /// 1-based line number `line` of that source
+    pub(crate) fn at(source: &'a str, path: Option<Arc<Path>>, base: usize, line: usize) -> Self {
+        Self {
+            source,
+            base,
+            offset: 0,
+            line,
+            path,
+        }
+    }
+}
+
+impl<'a> Iterator for EscapedLines<'a> {
+    type Item = EscapedLine<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let s = &self.source[self.offset..];
+            if s.is_empty() {
+                return None;
+            }
+
+            let (text, leftover, removed) = till_end_of_line(s);
+            let raw = &s[..leftover];
+            let raw = raw.strip_suffix('\n').unwrap_or(raw);
+
+            let start = self.base + self.offset;
+            let line = self.line;
+            self.offset += leftover;
+            self.line += s[..leftover].matches('\n').count();
+
+            let trimmed = text.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            break Some(EscapedLine {
+                text,
+                raw,
+                start,
+                line,
+                removed,
+                path: self.path.clone(),
+            });
+        }
+    }
+}
 
 pub(crate) fn skip_whitespace(s: &str) -> (&str, usize) {
     let mut chars = s.chars();
@@ -33,18 +161,30 @@ pub(crate) fn escape_end_in_str(
     end_char: char,
     escaped_replacement: Option<u8>,
 ) -> (Cow<str>, Option<usize>) {
+    let (taken, end, _) = escape_end_in_str_with_removed(s, end_char, escaped_replacement);
+    (taken, end)
+}
+
+/// Same as [`escape_end_in_str`], but also returns the byte offsets in `s` of the removed escape
+/// characters.
+fn escape_end_in_str_with_removed(
+    s: &str,
+    end_char: char,
+    escaped_replacement: Option<u8>,
+) -> (Cow<'_, str>, Option<usize>, Vec<usize>) {
     debug_assert!(end_char.is_ascii());
 
     let mut escaped_ends = Vec::with_capacity(0);
 
     if s.is_empty() {
-        return (Cow::Owned(String::with_capacity(0)), None);
+        return (Cow::Owned(String::with_capacity(0)), None, Vec::new());
     }
 
-    let mut escaped = false;
-
-    // 1. Find all the positions of escaped characters
+    // 1. Find all the positions of escaped end characters
+    // NOTE: Like Linux-PAM, only a backslash right in front of the end character is an escape,
+    // any other backslash is taken literally.
     let mut chars = s.chars();
+    let mut previous = None;
     let end = loop {
         // Fetch the byte index of the current char
         let i = s.len() - chars.as_str().len();
@@ -52,13 +192,14 @@ pub(crate) fn escape_end_in_str(
             break None;
         };
 
-        if escaped && (c == '\\' || c == end_char) {
+        if c == end_char {
+            if previous != Some('\\') {
+                break Some(i);
+            }
             escaped_ends.push(i);
-        } else if c == end_char {
-            break Some(i);
         }
 
-        escaped = !escaped && c == '\\';
+        previous = Some(c);
     };
 
     let (s, end) = if let Some(end) = end {
@@ -69,9 +210,11 @@ pub(crate) fn escape_end_in_str(
 
     // If no escaped characters were found, return.
     if escaped_ends.is_empty() {
-        return (Cow::Borrowed(s), end);
+        return (Cow::Borrowed(s), end, escaped_ends);
     }
 
+    let removed = escaped_ends.iter().map(|offset| offset - 1).collect();
+
     // 2. Remove all escaped new lines and replace them with spaces
     let mut s = String::from(s).into_bytes();
     for (index, offset) in escaped_ends.into_iter().enumerate() {
@@ -87,15 +230,16 @@ pub(crate) fn escape_end_in_str(
     }
     let s = unsafe { String::from_utf8_unchecked(s) };
 
-    (Cow::Owned(s), end)
+    (Cow::Owned(s), end, removed)
 }
 
 /// Takes a string until the end of the line. If an escaped line-feed is encountered it is
-/// converted into a space character.
-pub(crate) fn till_end_of_line(s: &str) -> (Cow<str>, usize) {
-    let (taken, end_of_line) = escape_end_in_str(s, '\n', Some(b' '));
+/// converted into a space character. The byte offsets of the removed escape characters are
+/// returned as well.
+pub(crate) fn till_end_of_line(s: &str) -> (Cow<'_, str>, usize, Vec<usize>) {
+    let (taken, end_of_line, removed) = escape_end_in_str_with_removed(s, '\n', Some(b' '));
     let end_of_line = end_of_line.unwrap_or(s.len());
-    (taken, end_of_line)
+    (taken, end_of_line, removed)
 }
 
 pub(crate) fn take_control_string(s: &str) -> Option<(&str, usize)> {
@@ -129,7 +273,8 @@ pub(crate) fn take_string(s: &str) -> Option<(Cow<str>, usize)> {
     Some((taken, end_of_line? + 1))
 }
 
-pub(crate) fn take_all_strings(s: &str) -> Option<Vec<Cow<str>>> {
+/// Takes all strings until the end of `s` together with the byte range of each string in `s`
+pub(crate) fn take_all_strings(s: &str) -> Option<Vec<(Cow<str>, Range<usize>)>> {
     let mut strings = Vec::new();
     let mut start_item = 0;
     loop {
@@ -146,11 +291,39 @@ pub(crate) fn take_all_strings(s: &str) -> Option<Vec<Cow<str>>> {
             return Some(strings);
         }
 
-        strings.push(taken);
+        let end_item = if item_s.starts_with('[') {
+            start_item + length
+        } else {
+            start_item + taken.len()
+        };
+
+        strings.push((taken, start_item..end_item));
         start_item += length;
     }
 }
 
+/// Escapes `s` so that it is read back as `s` by [`take_string`] after the line it is on has
+/// been read by [`till_end_of_line`]
+///
+/// Strings that contain whitespace or start with a `[` are put within brackets, where a `]` is
+/// escaped as `\\]`. Backslashes are written as is, so this is only faithful if `s` passes
+/// [`is_escapable`].
+pub(crate) fn escape_string(s: &str) -> Cow<'_, str> {
+    if s.starts_with('[') || s.contains([' ', '\t']) {
+        Cow::Owned(format!("[{}]", s.replace(']', "\\]")))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+/// Get whether [`escape_string`] can write `s` so that it is read back as `s`
+///
+/// An empty string is written as nothing. Otherwise a string cannot contain a new line and cannot
+/// end with a backslash, which would escape the new line or the `]` after it.
+pub(crate) fn is_escapable(s: &str) -> bool {
+    !s.is_empty() && !s.contains(['\n', '\r']) && !s.ends_with('\\')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,7 +332,7 @@ mod tests {
     fn till_eol() {
         macro_rules! assert_test {
             ($s:literal => $taken:literal, $length:literal) => {
-                let (taken, length) = till_end_of_line($s);
+                let (taken, length, _) = till_end_of_line($s);
 
                 let taken = String::from(taken);
 
@@ -173,6 +346,7 @@ mod tests {
         assert_test!("abc\n" => "abc", 4);
         assert_test!("abc\\\n" => "abc ", 5);
         assert_test!("abc\\\n\\\n" => "abc  ", 7);
+        assert_test!("abc\\\\xyz\n" => "abc\\\\xyz", 9);
         assert_test!("abc\nxyz" => "abc", 4);
         assert_test!("abc\nxyz\n123" => "abc", 4);
         assert_test!("abc\\\nxyz\n123" => "abc xyz", 9);
@@ -262,6 +436,7 @@ mod tests {
         assert_test!("abc xyz" => "abc", 4);
         assert_test!("[abc xyz] 123" => "abc xyz", 9);
         assert_test!("[abc \\]xyz] 123" => "abc ]xyz", 11);
+        assert_test!("[a\\\\]b] 123" => "a\\]b", 7);
         assert_test!("[...[...\\]...] 123" => "...[...]...", 14);
         assert_test!("[ 123" => !);
     }
@@ -277,7 +452,7 @@ mod tests {
                 assert!(strings.is_some());
                 let strings = strings.unwrap();
 
-                let strings: Vec<String> = strings.into_iter().map(|s| String::from(s)).collect();
+                let strings: Vec<String> = strings.into_iter().map(|(s, _)| String::from(s)).collect();
                 let eq: Vec<&str> = vec![$($taken,)*];
 
                 assert_eq!(strings, eq);
@@ -293,4 +468,34 @@ mod tests {
         assert_test!("[...[...\\]...] 123" => ["...[...]...", "123"]);
         assert_test!("[ 123" => !);
     }
+
+    #[test]
+    fn escape_str() {
+        macro_rules! assert_test {
+            ($s:literal => $escaped:literal) => {
+                let escaped = escape_string($s);
+                assert_eq!(escaped, $escaped);
+
+                let (line, _, _) = till_end_of_line(&escaped);
+                let (taken, _) = take_string(&line).unwrap();
+                assert_eq!(taken, $s);
+            };
+        }
+
+        assert_test!("abc" => "abc");
+        assert_test!("a]b" => "a]b");
+        assert_test!("a\\b" => "a\\b");
+        assert_test!("abc xyz" => "[abc xyz]");
+        assert_test!("[abc" => "[[abc]");
+        assert_test!("a b]c" => "[a b\\]c]");
+        assert_test!("a\\\\b" => "a\\\\b");
+        assert_test!("a\\ b" => "[a\\ b]");
+        assert_test!("a\\ b]" => "[a\\ b\\]]");
+        assert_test!("a\\]b c" => "[a\\\\]b c]");
+
+        assert!(!is_escapable(""));
+        assert!(!is_escapable("a\\"));
+        assert!(!is_escapable("a b\\"));
+        assert!(!is_escapable("a\nb"));
+    }
 }
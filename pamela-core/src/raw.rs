@@ -0,0 +1,756 @@
+//! Byte-exact parsing of configurations in the same way as Linux-PAM
+//!
+//! The other parsers in this crate work on `&str` and reject anything that is not valid syntax.
+//! Linux-PAM instead reads its configuration as raw bytes with `_pam_assemble_line`,
+//! `_pam_StrTok` and `_pam_parse_control` in `pam_handlers.c`, and installs a broken rule such
+//! that it always fails. The [`RawRule`]s of this module are tokenized with the same algorithm,
+//! including its odd cases:
+//!
+//! - A `#` anywhere on a line starts a comment, even within `[...]`.
+//! - A backslash that is only followed by spaces and tabs continues the line, and blank and
+//!   comment lines in between are skipped. A line that is still continued at the end of the file
+//!   is dropped.
+//! - `[...]` is one token, which ends at the first `]`. Only `\]` is an escape, so `\\` stays
+//!   two backslashes, and `a[b]` or `[a]b` are two tokens.
+//! - Domains, presets, `include` and `substack` are case-insensitive, and a preset may be put in
+//!   brackets, e.g. `[required]`.
+//! - A selection that cannot be parsed makes every return code `die`, and the return codes and
+//!   actions are matched by prefix, e.g. `okay` is `ok` followed by garbage. A jump of `0` is
+//!   `ignore`.
+//! - An unknown or missing domain, a missing control and a missing module do not reject the rule,
+//!   but make it fail when it runs. See [`RawFault`].
+//!
+//! Bytes that are not UTF-8 are kept as they are, so a file with Latin-1 comments or arguments
+//! parses just like it does for Linux-PAM.
+
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::{
+    Action, Control, Domain, InclusionMethod, ModuleArgument, ModulePath, PamConfigError, PamRule,
+    PamService, ReturnCode, RuleContent, Selection, SelectionItem, Span, Value,
+};
+
+/// Rule of a configuration that is tokenized byte-exact in the same way as Linux-PAM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawRule {
+    line: usize,
+    range: Range<usize>,
+    service: Option<Vec<u8>>,
+    is_logging_enabled: bool,
+    domain: Option<Domain>,
+    content: RawContent,
+    fault: Option<RawFault>,
+}
+
+/// What a [`RawRule`] does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawContent {
+    /// Use the rules of another service
+    ServiceInclusion {
+        method: InclusionMethod,
+        service: Vec<u8>,
+    },
+    /// Call a module
+    Entry {
+        control: Control,
+        module_path: Vec<u8>,
+        module_arguments: Vec<Vec<u8>>,
+    },
+}
+
+/// Reason why Linux-PAM installs a [`RawRule`] such that it always fails when it runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawFault {
+    /// The rule has no domain, so Linux-PAM uses it in whichever domain is requested
+    MissingDomain,
+    /// The domain is not known, so Linux-PAM uses it in the `auth` domain
+    UnknownDomain(Vec<u8>),
+    /// The rule has no control, so every return code is `bad`
+    MissingControl,
+    /// The rule has no module path or no service to include
+    MissingModule,
+}
+
+/// A service file that is parsed byte-exact in the same way as Linux-PAM
+///
+/// Unlike a [`PamService`], the name does not need to be valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawService {
+    name: OsString,
+    rules: Vec<RawRule>,
+}
+
+/// Iterator over the rules of a configuration in raw bytes, created with
+/// [`RawRule::packed_iter`] or [`RawRule::separated_iter`]
+pub struct RawRuleIterator<'a> {
+    source: &'a [u8],
+    offset: usize,
+    line: usize,
+    is_packed: bool,
+}
+
+/// Logical line as `_pam_assemble_line` puts it together
+struct AssembledLine {
+    text: Vec<u8>,
+    range: Range<usize>,
+    line: usize,
+}
+
+impl RawRule {
+    /// Create an iterator over the rules in the packed configuration format
+    ///
+    /// The name of the service of every rule is in [`RawRule::service`]. Since Linux-PAM never
+    /// rejects a rule, there are no errors.
+    pub fn packed_iter(source: &[u8]) -> RawRuleIterator<'_> {
+        RawRuleIterator::new(source, true)
+    }
+
+    /// Create an iterator over the rules in the separated configuration format
+    ///
+    /// Since Linux-PAM never rejects a rule, there are no errors.
+    pub fn separated_iter(source: &[u8]) -> RawRuleIterator<'_> {
+        RawRuleIterator::new(source, false)
+    }
+
+    /// Get the 1-based line number where this rule starts
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Get the byte range of the lines of this rule, without the final new line
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Get the name of the service, which is only `Some` in the packed configuration format
+    pub fn service(&self) -> Option<&[u8]> {
+        self.service.as_deref()
+    }
+
+    /// Get whether to log for this rule or not
+    pub fn is_logging_enabled(&self) -> bool {
+        self.is_logging_enabled
+    }
+
+    /// Get the [`Domain`], which is `None` for an `@include` directive or a missing domain
+    pub fn domain(&self) -> Option<Domain> {
+        self.domain
+    }
+
+    /// Get what this rule does
+    pub fn content(&self) -> &RawContent {
+        &self.content
+    }
+
+    /// Get why this rule always fails, which is `None` for a rule that works
+    pub fn fault(&self) -> Option<&RawFault> {
+        self.fault.as_ref()
+    }
+
+    /// Get the control parameters, which is `None` for a rule that includes another service
+    pub fn control(&self) -> Option<&Control> {
+        match &self.content {
+            RawContent::Entry { control, .. } => Some(control),
+            RawContent::ServiceInclusion { .. } => None,
+        }
+    }
+
+    /// Get the module path, which is `None` for a rule that includes another service
+    pub fn module_path(&self) -> Option<&[u8]> {
+        match &self.content {
+            RawContent::Entry { module_path, .. } => Some(module_path),
+            RawContent::ServiceInclusion { .. } => None,
+        }
+    }
+
+    /// Get the module arguments, which is empty for a rule that includes another service
+    pub fn module_arguments(&self) -> &[Vec<u8>] {
+        match &self.content {
+            RawContent::Entry {
+                module_arguments, ..
+            } => module_arguments,
+            RawContent::ServiceInclusion { .. } => &[],
+        }
+    }
+
+    /// Convert into a [`PamRule`], replacing bytes that are not UTF-8 with `U+FFFD`
+    ///
+    /// A rule with a [`RawFault`] cannot be represented as a [`PamRule`] and gives `None`.
+    pub fn to_rule_lossy(&self) -> Option<PamRule> {
+        if self.fault.is_some() {
+            return None;
+        }
+
+        let lossy = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+
+        let content = match &self.content {
+            RawContent::ServiceInclusion { method, service } => RuleContent::ServiceInclusion {
+                method: *method,
+                service: lossy(service),
+            },
+            RawContent::Entry {
+                control,
+                module_path,
+                module_arguments,
+            } => RuleContent::Entry {
+                control: control.clone(),
+                module_path: ModulePath::new_unchecked(lossy(module_path)),
+                module_arguments: module_arguments
+                    .iter()
+                    .map(|argument| {
+                        let argument = lossy(argument);
+                        match argument.find('=') {
+                            Some(position) if position > 0 => ModuleArgument::KeyValue {
+                                key: argument[..position].to_string(),
+                                value: argument[position + 1..].to_string(),
+                            },
+                            _ => ModuleArgument::Set(argument),
+                        }
+                    })
+                    .collect(),
+            },
+        };
+
+        Some(PamRule {
+            span: Span::new(None, self.range.clone(), self.line, 1),
+            is_logging_enabled: self.is_logging_enabled,
+            domain: self.domain,
+            content,
+        })
+    }
+}
+
+impl RawService {
+    /// Parse a [`RawService`] named `name` from the raw contents of its service file
+    pub fn from_bytes(name: impl AsRef<OsStr>, source: &[u8]) -> Self {
+        Self {
+            name: name.as_ref().to_os_string(),
+            rules: RawRule::separated_iter(source).collect(),
+        }
+    }
+
+    /// Read a [`RawService`] from a service file
+    ///
+    /// In contrast to [`PamService::from_file`], neither the file name nor the contents need to
+    /// be valid UTF-8.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PamConfigError> {
+        let path = path.as_ref();
+        let name = path.file_name().ok_or(PamConfigError::NotAFilename)?;
+        let source = fs::read(path)?;
+
+        Ok(Self::from_bytes(name, &source))
+    }
+
+    /// Get the name of the service
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// Get the rules of the service
+    pub fn rules(&self) -> &[RawRule] {
+        &self.rules
+    }
+
+    /// Convert into a [`PamService`], replacing bytes that are not UTF-8 with `U+FFFD`
+    ///
+    /// The rules with a [`RawFault`] cannot be represented and are returned next to the
+    /// [`PamService`].
+    pub fn to_service_lossy(&self) -> (PamService, Vec<&RawRule>) {
+        let mut rules = Vec::new();
+        let mut faulty = Vec::new();
+        for rule in &self.rules {
+            match rule.to_rule_lossy() {
+                Some(converted) => rules.push(converted),
+                None => faulty.push(rule),
+            }
+        }
+
+        let service = PamService::new(&self.name.to_string_lossy(), rules);
+        (service, faulty)
+    }
+}
+
+impl<'a> RawRuleIterator<'a> {
+    fn new(source: &'a [u8], is_packed: bool) -> Self {
+        Self {
+            source,
+            offset: 0,
+            line: 1,
+            is_packed,
+        }
+    }
+
+    /// Take the next physical line including its new line, up to the first NUL byte
+    ///
+    /// Linux-PAM reads the line with `fgets`, but then treats it as a C string.
+    fn next_physical_line(&mut self) -> Option<(&'a [u8], usize)> {
+        if self.offset >= self.source.len() {
+            return None;
+        }
+
+        let rest = &self.source[self.offset..];
+        let length = rest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(rest.len(), |position| position + 1);
+        let offset = self.offset;
+
+        self.offset += length;
+        self.line += 1;
+
+        let line = &rest[..length];
+        let line = match line.iter().position(|&byte| byte == 0) {
+            Some(position) => &line[..position],
+            None => line,
+        };
+        Some((line, offset))
+    }
+
+    /// Put together the next line in the same way as `_pam_assemble_line`
+    fn next_line(&mut self) -> Option<AssembledLine> {
+        let mut assembled: Option<AssembledLine> = None;
+
+        loop {
+            let line_number = self.line;
+            // NOTE: Linux-PAM drops a line that is still continued at the end of the file
+            let (physical, offset) = self.next_physical_line()?;
+
+            let start = physical
+                .iter()
+                .position(|byte| !matches!(byte, b' ' | b'\n' | b'\t'))
+                .unwrap_or(physical.len());
+            let text = &physical[start..];
+
+            // Blank and comment lines are skipped, even within a continued line
+            if text.is_empty() || text[0] == b'#' {
+                continue;
+            }
+
+            let end = offset + physical.len() - usize::from(physical.ends_with(b"\n"));
+            let assembled = assembled.get_or_insert_with(|| AssembledLine {
+                text: Vec::new(),
+                range: offset + start..end,
+                line: line_number,
+            });
+            assembled.range.end = end;
+
+            if let Some(comment) = text.iter().position(|&byte| byte == b'#') {
+                assembled.text.extend_from_slice(&text[..comment]);
+                break;
+            }
+
+            let content_length = text
+                .iter()
+                .rposition(|byte| !matches!(byte, b' ' | b'\t' | b'\n'))
+                .map_or(0, |position| position + 1);
+            if text[content_length - 1] == b'\\' {
+                // The backslash becomes a space and the rest of the line is dropped
+                assembled
+                    .text
+                    .extend_from_slice(&text[..content_length - 1]);
+                assembled.text.push(b' ');
+                continue;
+            }
+
+            assembled.text.extend_from_slice(text);
+            break;
+        }
+
+        assembled
+    }
+}
+
+impl Iterator for RawRuleIterator<'_> {
+    type Item = RawRule;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.next_line()?;
+        let mut tokens = Tokens {
+            text: &line.text,
+            position: 0,
+        };
+
+        // The line is not empty, so there is always a first token
+        let service = if self.is_packed { tokens.next() } else { None };
+
+        let mut rule = RawRule {
+            line: line.line,
+            range: line.range,
+            service,
+            is_logging_enabled: true,
+            domain: None,
+            content: RawContent::Entry {
+                control: Control::Selection(uniform_selection(Action::Bad)),
+                module_path: Vec::new(),
+                module_arguments: Vec::new(),
+            },
+            fault: None,
+        };
+
+        let Some(mut domain) = tokens.next() else {
+            rule.fault = Some(RawFault::MissingDomain);
+            return Some(rule);
+        };
+
+        if domain == b"@include" {
+            match tokens.next() {
+                Some(included) => {
+                    rule.content = RawContent::ServiceInclusion {
+                        method: InclusionMethod::IncludeDirective,
+                        service: included,
+                    }
+                }
+                None => rule.fault = Some(RawFault::MissingModule),
+            }
+            return Some(rule);
+        }
+
+        if domain.first() == Some(&b'-') {
+            rule.is_logging_enabled = false;
+            domain.remove(0);
+        }
+
+        rule.domain = Some(match domain.to_ascii_lowercase().as_slice() {
+            b"account" => Domain::Account,
+            b"auth" => Domain::Auth,
+            b"password" => Domain::Password,
+            b"session" => Domain::Session,
+            _ => {
+                rule.fault = Some(RawFault::UnknownDomain(domain));
+                Domain::Auth
+            }
+        });
+
+        let Some(control) = tokens.next() else {
+            rule.fault.get_or_insert(RawFault::MissingControl);
+            return Some(rule);
+        };
+
+        let control = match control.to_ascii_lowercase().as_slice() {
+            b"required" => Control::Required,
+            b"requisite" => Control::Requisite,
+            b"optional" => Control::Optional,
+            b"sufficient" => Control::Sufficient,
+            method @ (b"include" | b"substack") => {
+                let method = if method == b"include" {
+                    InclusionMethod::Include
+                } else {
+                    InclusionMethod::Substack
+                };
+
+                match tokens.next() {
+                    Some(included) => {
+                        rule.content = RawContent::ServiceInclusion {
+                            method,
+                            service: included,
+                        }
+                    }
+                    None => {
+                        rule.fault.get_or_insert(RawFault::MissingModule);
+                    }
+                }
+                return Some(rule);
+            }
+            _ => Control::Selection(parse_selection(&control)),
+        };
+
+        let module_path = tokens.next().unwrap_or_else(|| {
+            rule.fault.get_or_insert(RawFault::MissingModule);
+            Vec::new()
+        });
+
+        rule.content = RawContent::Entry {
+            control,
+            module_path,
+            module_arguments: tokens.collect(),
+        };
+
+        Some(rule)
+    }
+}
+
+/// Tokenizer in the same way as `_pam_StrTok` with the delimiters space, tab and new line
+struct Tokens<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let is_delimiter = |byte: u8| matches!(byte, b' ' | b'\t' | b'\n');
+
+        let rest = &self.text[self.position..];
+        let start = rest.iter().position(|&byte| !is_delimiter(byte))?;
+        let rest = &rest[start..];
+
+        let (token, length) = if rest[0] == b'[' {
+            // Everything up to the first `]` is one token, and only `\]` is an escape
+            let mut token = Vec::new();
+            let mut index = 1;
+            while index < rest.len() && rest[index] != b']' {
+                if rest[index] == b'\\' && rest.get(index + 1) == Some(&b']') {
+                    index += 1;
+                }
+                token.push(rest[index]);
+                index += 1;
+            }
+            (token, index)
+        } else {
+            let length = rest
+                .iter()
+                .position(|&byte| is_delimiter(byte))
+                .unwrap_or(rest.len());
+            (rest[..length].to_vec(), length)
+        };
+
+        // The byte that ends the token is consumed as well
+        self.position = (self.position + start + length + 1).min(self.text.len());
+        Some(token)
+    }
+}
+
+/// Create a selection with the same action for every [`ReturnCode`]
+fn uniform_selection(action: Action) -> Selection {
+    Selection::new(
+        ReturnCode::ALL
+            .into_iter()
+            .map(|return_code| SelectionItem::new(return_code.into(), action))
+            .collect(),
+    )
+}
+
+/// Skip the leading bytes for which C's `isspace` is true
+fn skip_space(s: &[u8]) -> &[u8] {
+    let length = s
+        .iter()
+        .take_while(|byte| matches!(byte, b' ' | b'\t'..=b'\r'))
+        .count();
+    &s[length..]
+}
+
+/// Parse the items of a selection in the same way as `_pam_parse_control`
+///
+/// The result has an item for every [`ReturnCode`]. A selection that cannot be parsed makes every
+/// return code `die`.
+fn parse_selection(mut s: &[u8]) -> Selection {
+    let mut actions: [Option<Action>; ReturnCode::ALL.len()] = [None; ReturnCode::ALL.len()];
+
+    let mut parse = || -> Option<()> {
+        loop {
+            s = skip_space(s);
+            if s.is_empty() {
+                return Some(());
+            }
+
+            // The return codes are matched by prefix, followed by `default`
+            let (index, name) = ReturnCode::ALL
+                .iter()
+                .map(|&return_code| <&'static str>::from(Value::from(return_code)))
+                .chain(["default"])
+                .enumerate()
+                .find(|(_, name)| s.starts_with(name.as_bytes()))?;
+            s = &s[name.len()..];
+            if s.is_empty() {
+                return None;
+            }
+
+            s = skip_space(s);
+            s = s.strip_prefix(b"=")?;
+            s = skip_space(s);
+
+            let action = [
+                ("ignore", Action::Ignore),
+                ("ok", Action::Ok),
+                ("done", Action::Done),
+                ("bad", Action::Bad),
+                ("die", Action::Die),
+                ("reset", Action::Reset),
+            ]
+            .into_iter()
+            .find(|(name, _)| s.starts_with(name.as_bytes()));
+
+            let action = match action {
+                Some((name, action)) => {
+                    s = &s[name.len()..];
+                    action
+                }
+                None => {
+                    let digits = s.iter().take_while(|byte| byte.is_ascii_digit()).count();
+                    let jump = std::str::from_utf8(&s[..digits])
+                        .ok()?
+                        .parse::<i32>()
+                        .ok()?;
+                    s = &s[digits..];
+                    // Jumping over no rules continues with the next one, like pam.conf(5) says
+                    match jump {
+                        0 => Action::Ignore,
+                        _ => Action::JumpOver(jump as u32),
+                    }
+                }
+            };
+
+            match actions.get_mut(index) {
+                Some(slot) => *slot = Some(action),
+                None => actions
+                    .iter_mut()
+                    .filter(|slot| slot.is_none())
+                    .for_each(|slot| *slot = Some(action)),
+            }
+        }
+    };
+
+    if parse().is_none() {
+        return uniform_selection(Action::Die);
+    }
+
+    Selection::new(
+        ReturnCode::ALL
+            .into_iter()
+            .zip(actions)
+            .map(|(return_code, action)| {
+                SelectionItem::new(return_code.into(), action.unwrap_or(Action::Bad))
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(source: &[u8]) -> Vec<RawRule> {
+        RawRule::separated_iter(source).collect()
+    }
+
+    #[test]
+    fn non_utf8() {
+        let source = b"# Gr\xfc\xdfe\nauth required pam_unix.so motd=/etc/gr\xfc\xdf\n";
+        let rules = rules(source);
+
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].line(), 2);
+        assert_eq!(rules[0].module_path(), Some(&b"pam_unix.so"[..]));
+        assert_eq!(
+            rules[0].module_arguments(),
+            [b"motd=/etc/gr\xfc\xdf".to_vec()]
+        );
+
+        let rule = rules[0].to_rule_lossy().unwrap();
+        assert!(matches!(
+            &rule.module_arguments()[0],
+            ModuleArgument::KeyValue { key, value } if key == "motd" && value == "/etc/gr\u{fffd}\u{fffd}"
+        ));
+    }
+
+    #[test]
+    fn tokenization() {
+        let source = b"AUTH [Required] pam_unix.so a[b] [c]d [x#y]\n\
+                       auth required pam_env.so [a\\\\]b] [c\\]d]\n\
+                       auth required \\  \n\
+                       \n\
+                       # comment\n\
+                       \tpam_deny.so\n\
+                       auth required \\\n";
+        let rules = rules(source);
+
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].domain(), Some(Domain::Auth));
+        assert_eq!(rules[0].control(), Some(&Control::Required));
+        assert_eq!(
+            rules[0].module_arguments(),
+            [
+                b"a[b]".to_vec(),
+                b"c".to_vec(),
+                b"d".to_vec(),
+                b"x".to_vec()
+            ]
+        );
+        assert_eq!(
+            rules[1].module_arguments(),
+            [b"a\\]b".to_vec(), b"c]d".to_vec()]
+        );
+        assert_eq!(rules[2].line(), 3);
+        assert_eq!(rules[2].module_path(), Some(&b"pam_deny.so"[..]));
+        assert_eq!(rules[2].range(), 84..125);
+    }
+
+    #[test]
+    fn selections() {
+        let control = |source: &[u8]| rules(source)[0].control().unwrap().clone();
+        let action = |control: &Control, return_code: ReturnCode| {
+            control
+                .to_selection()
+                .items()
+                .iter()
+                .find(|item| item.value() == return_code.into())
+                .unwrap()
+                .action()
+        };
+
+        let selection = control(b"auth [success = 2 default=ignore] pam_unix.so\n");
+        assert_eq!(action(&selection, ReturnCode::Success), Action::JumpOver(2));
+        assert_eq!(action(&selection, ReturnCode::UserUnknown), Action::Ignore);
+
+        let selection = control(b"auth success=ok pam_unix.so\n");
+        assert_eq!(action(&selection, ReturnCode::Success), Action::Ok);
+        assert_eq!(action(&selection, ReturnCode::UserUnknown), Action::Bad);
+
+        let selection = control(b"auth [success=0 default=1] pam_unix.so\n");
+        assert_eq!(action(&selection, ReturnCode::Success), Action::Ignore);
+        assert_eq!(
+            action(&selection, ReturnCode::UserUnknown),
+            Action::JumpOver(1)
+        );
+
+        for invalid in [
+            &b"auth [success=okay] pam_unix.so\n"[..],
+            b"auth sometimes pam_unix.so\n",
+        ] {
+            let selection = control(invalid);
+            assert!(selection
+                .to_selection()
+                .items()
+                .iter()
+                .all(|item| item.action() == Action::Die));
+        }
+    }
+
+    #[test]
+    fn faults() {
+        let source = b"login\n\
+                       login sessions required pam_env.so\n\
+                       login -Session\n\
+                       login account include\n\
+                       login @include common-auth\n";
+        let rules: Vec<_> = RawRule::packed_iter(source).collect();
+
+        assert_eq!(rules.len(), 5);
+        assert!(rules
+            .iter()
+            .all(|rule| rule.service() == Some(&b"login"[..])));
+        assert_eq!(rules[0].fault(), Some(&RawFault::MissingDomain));
+        assert_eq!(
+            rules[1].fault(),
+            Some(&RawFault::UnknownDomain(b"sessions".to_vec()))
+        );
+        assert_eq!(rules[1].domain(), Some(Domain::Auth));
+        assert_eq!(rules[2].fault(), Some(&RawFault::MissingControl));
+        assert!(!rules[2].is_logging_enabled());
+        assert_eq!(rules[3].fault(), Some(&RawFault::MissingModule));
+        assert_eq!(rules[4].fault(), None);
+
+        let service = RawService::from_bytes(
+            "login",
+            b"auth required pam_env.so\nauth\nauth include common-auth\n",
+        );
+        let (converted, faulty) = service.to_service_lossy();
+        assert_eq!(converted.name(), "login");
+        assert_eq!(converted.rules().len(), 2);
+        assert_eq!(faulty, [&service.rules()[1]]);
+    }
+}
@@ -0,0 +1,380 @@
+//! Incremental parsing of rules from an [`io::BufRead`]
+//!
+//! The readers in this module yield rules as soon as their line has been read, without reading
+//! the whole source into memory first. A rule that is continued with a backslash-newline is
+//! put together from as many reads as needed. Since the source may not be trusted, e.g. a file
+//! within a container image, the length of a line and the number of rules are bounded by
+//! [`ReadLimits`].
+
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::parsing::EscapedLines;
+use crate::{take_packed_rule, take_rule, PamConfig, PamConfigError, PamRule, PamService};
+
+/// Bounds on the input of a [`PackedRuleReader`] or [`SeparatedRuleReader`]
+///
+/// The [`Default`] allows lines of 64 KiB and 10 000 rules, which is far more than any real
+/// configuration needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadLimits {
+    max_line_length: usize,
+    max_rules: usize,
+}
+
+/// Incremental parser of the packed configuration format, created with
+/// [`PamRule::packed_reader`]
+///
+/// See [`PackedRuleIterator`](crate::PackedRuleIterator) for the syntax. The iterator stops
+/// after the first I/O error or exceeded limit.
+pub struct PackedRuleReader<R>(LogicalLines<R>);
+
+/// Incremental parser of the separated configuration format, created with
+/// [`PamRule::separated_reader`]
+///
+/// See [`SeparatedRuleIterator`](crate::SeparatedRuleIterator) for the syntax. The iterator
+/// stops after the first I/O error or exceeded limit.
+pub struct SeparatedRuleReader<R>(LogicalLines<R>);
+
+/// Reader of lines with all line continuations, which keeps track of their position
+struct LogicalLines<R> {
+    reader: R,
+    limits: ReadLimits,
+    path: Option<Arc<Path>>,
+    offset: usize,
+    line: usize,
+    rules: usize,
+    is_done: bool,
+}
+
+impl ReadLimits {
+    /// Create limits for the length of a line in bytes, including its continuations, and the
+    /// number of rules
+    ///
+    /// Lines with a syntax error count as rules as well.
+    pub const fn new(max_line_length: usize, max_rules: usize) -> Self {
+        Self {
+            max_line_length,
+            max_rules,
+        }
+    }
+
+    /// Get the maximum length of a line in bytes, including its continuations
+    pub fn max_line_length(&self) -> usize {
+        self.max_line_length
+    }
+
+    /// Get the maximum number of rules
+    pub fn max_rules(&self) -> usize {
+        self.max_rules
+    }
+}
+
+impl Default for ReadLimits {
+    fn default() -> Self {
+        Self::new(64 * 1024, 10_000)
+    }
+}
+
+impl PamRule {
+    /// Create an incremental parser of the packed configuration format
+    ///
+    /// This is the same as [`PamRule::packed_iter`], except that the rules are read from
+    /// `reader` while iterating.
+    pub fn packed_reader<R: BufRead>(reader: R) -> PackedRuleReader<R> {
+        PackedRuleReader(LogicalLines::new(reader))
+    }
+
+    /// Create an incremental parser of the separated configuration format
+    ///
+    /// This is the same as [`PamRule::separated_iter`], except that the rules are read from
+    /// `reader` while iterating.
+    pub fn separated_reader<R: BufRead>(reader: R) -> SeparatedRuleReader<R> {
+        SeparatedRuleReader(LogicalLines::new(reader))
+    }
+}
+
+impl PamConfig {
+    /// Read a [`PamConfig`] in the packed configuration syntax from a reader
+    ///
+    /// The input is bounded by the [`Default`] [`ReadLimits`], use [`PamRule::packed_reader`]
+    /// for other limits.
+    pub fn from_reader(reader: impl BufRead) -> Result<PamConfig, PamConfigError> {
+        let packed_rules = PamRule::packed_reader(reader)
+            .collect::<Result<Vec<(String, PamRule)>, PamConfigError>>()?;
+        Ok(Self::from_packed_rules(packed_rules))
+    }
+}
+
+impl PamService {
+    /// Read a [`PamService`] named `name` in the separated configuration syntax from a reader
+    ///
+    /// The input is bounded by the [`Default`] [`ReadLimits`], use [`PamRule::separated_reader`]
+    /// for other limits.
+    pub fn from_reader(name: &str, reader: impl BufRead) -> Result<PamService, PamConfigError> {
+        let rules =
+            PamRule::separated_reader(reader).collect::<Result<Vec<PamRule>, PamConfigError>>()?;
+        Ok(PamService::new(name, rules))
+    }
+}
+
+macro_rules! reader_options {
+    ($($reader:ident),*) => {
+        $(
+        impl<R: BufRead> $reader<R> {
+            /// Set the bounds on the input
+            pub fn with_limits(mut self, limits: ReadLimits) -> Self {
+                self.0.limits = limits;
+                self
+            }
+
+            /// Set the path that ends up in the [`Span`](crate::Span)s of the rules
+            pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+                self.0.path = Some(Arc::from(path.as_ref()));
+                self
+            }
+        }
+        )*
+    };
+}
+
+reader_options!(PackedRuleReader, SeparatedRuleReader);
+
+impl<R: BufRead> Iterator for PackedRuleReader<R> {
+    type Item = Result<(String, PamRule), PamConfigError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_rule(|lines| {
+            let escaped_line = lines.next()?;
+            let rule = take_packed_rule(&escaped_line, &escaped_line.text);
+            Some(rule.map(|(service_name, rule)| (service_name.into_owned(), rule.into_owned())))
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for SeparatedRuleReader<R> {
+    type Item = Result<PamRule, PamConfigError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_rule(|lines| {
+            let escaped_line = lines.next()?;
+            Some(take_rule(&escaped_line, &escaped_line.text).map(|rule| rule.into_owned()))
+        })
+    }
+}
+
+impl<R: BufRead> LogicalLines<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            limits: ReadLimits::default(),
+            path: None,
+            offset: 0,
+            line: 1,
+            rules: 0,
+            is_done: false,
+        }
+    }
+
+    /// Read lines until `take` gives a rule or a syntax error for one of them
+    fn next_rule<T, E>(
+        &mut self,
+        take: impl Fn(&mut EscapedLines) -> Option<Result<T, E>>,
+    ) -> Option<Result<T, PamConfigError>>
+    where
+        PamConfigError: From<E>,
+    {
+        while !self.is_done {
+            let (text, offset, line) = match self.read_line() {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(err) => {
+                    self.is_done = true;
+                    return Some(Err(err));
+                }
+            };
+
+            // Empty and comment lines give nothing
+            let mut lines = EscapedLines::at(&text, self.path.clone(), offset, line);
+            let Some(rule) = take(&mut lines) else {
+                continue;
+            };
+
+            if self.rules == self.limits.max_rules {
+                self.is_done = true;
+                return Some(Err(PamConfigError::TooManyRules(self.limits.max_rules)));
+            }
+            self.rules += 1;
+
+            return Some(rule.map_err(PamConfigError::from));
+        }
+
+        self.is_done = true;
+        None
+    }
+
+    /// Read the next line including its continuations and the final new line
+    ///
+    /// Returns the line together with its byte offset and 1-based line number.
+    fn read_line(&mut self) -> Result<Option<(String, usize, usize)>, PamConfigError> {
+        let mut buffer = Vec::new();
+
+        loop {
+            let start = buffer.len();
+            if !self.read_physical_line(&mut buffer)? {
+                break;
+            }
+
+            // NOTE: Like Linux-PAM, a backslash right before the new line continues the line,
+            // even if it follows another backslash.
+            if !buffer[start..buffer.len() - 1].ends_with(b"\\") {
+                break;
+            }
+        }
+
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+
+        let (offset, line) = (self.offset, self.line);
+        self.offset += buffer.len();
+        self.line += buffer.iter().filter(|&&byte| byte == b'\n').count();
+
+        let text = String::from_utf8(buffer)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        Ok(Some((text, offset, line)))
+    }
+
+    /// Append the next physical line to `buffer` and get whether it ended with a new line
+    fn read_physical_line(&mut self, buffer: &mut Vec<u8>) -> Result<bool, PamConfigError> {
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+
+            if available.is_empty() {
+                return Ok(false);
+            }
+
+            let (length, has_new_line) = match available.iter().position(|&byte| byte == b'\n') {
+                Some(position) => (position + 1, true),
+                None => (available.len(), false),
+            };
+
+            if buffer.len() + length > self.limits.max_line_length {
+                return Err(PamConfigError::LineTooLong {
+                    line: self.line,
+                    max_length: self.limits.max_line_length,
+                });
+            }
+
+            buffer.extend_from_slice(&available[..length]);
+            self.reader.consume(length);
+
+            if has_new_line {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+
+    const SERVICE: &str = r#"#%PAM-1.0
+auth    required    pam_env.so
+auth    [success=1 \
+         default=ignore]    pam_unix.so nullok \
+         [foo=a\]b]
+
+# Comment
+auth    requisite   pam_deny.so
+account include     system-account"#;
+
+    #[test]
+    fn continuations() {
+        let expected = PamService::from_str(SERVICE).unwrap();
+
+        for capacity in [1, 3, 16, 1024] {
+            let reader = BufReader::with_capacity(capacity, SERVICE.as_bytes());
+            let rules = PamRule::separated_reader(reader)
+                .collect::<Result<Vec<PamRule>, PamConfigError>>()
+                .unwrap();
+
+            assert_eq!(rules.len(), expected.len());
+            for (rule, expected) in rules.iter().zip(&expected) {
+                assert_eq!(rule.to_string(), expected.to_string());
+                assert_eq!(rule.span(), expected.span());
+            }
+        }
+
+        let service = PamService::from_reader("login", SERVICE.as_bytes()).unwrap();
+        assert_eq!(service.name(), "login");
+        assert_eq!(service.rules().len(), 4);
+
+        let config = PamConfig::from_reader(
+            "login auth required pam_unix.so\nsshd auth include login\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(config.services().len(), 2);
+
+        // The backslash in front of the one that continues the line is not an escape
+        let source = "auth required pam_env.so conffile=a\\\\\n readenv=1\n";
+        let rules = PamRule::separated_reader(source.as_bytes())
+            .collect::<Result<Vec<PamRule>, PamConfigError>>()
+            .unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].module_arguments().len(), 2);
+        assert_eq!(rules[0].module_arguments()[0].to_string(), "conffile=a\\");
+    }
+
+    #[test]
+    fn limits() {
+        let rules: Vec<_> = PamRule::separated_reader(SERVICE.as_bytes())
+            .with_limits(ReadLimits::new(128, 2))
+            .collect();
+        assert_eq!(rules.len(), 3);
+        assert!(rules[..2].iter().all(Result::is_ok));
+        assert!(matches!(rules[2], Err(PamConfigError::TooManyRules(2))));
+
+        let rules: Vec<_> = PamRule::separated_reader(SERVICE.as_bytes())
+            .with_limits(ReadLimits::new(32, 10))
+            .collect();
+        assert_eq!(rules.len(), 2);
+        assert!(matches!(
+            rules[1],
+            Err(PamConfigError::LineTooLong {
+                line: 3,
+                max_length: 32
+            })
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let source = "auth required pam_env.so\nauth sometimes pam_unix.so\n";
+        let rules: Vec<_> = PamRule::separated_reader(source.as_bytes())
+            .with_path("/etc/pam.d/login")
+            .collect();
+        let Err(PamConfigError::Syntax(error)) = &rules[1] else {
+            panic!("Expected a syntax error");
+        };
+        assert_eq!(error.span().path(), Some(Path::new("/etc/pam.d/login")));
+        assert_eq!(error.span().line(), 2);
+        assert_eq!(error.span().range(), 30..39);
+
+        let invalid = b"auth required pam_env.so\nauth required pam_\xff.so\n";
+        let rules: Vec<_> = PamRule::separated_reader(&invalid[..]).collect();
+        assert_eq!(rules.len(), 2);
+        assert!(
+            matches!(&rules[1], Err(PamConfigError::Io(err)) if err.kind() == io::ErrorKind::InvalidData)
+        );
+    }
+}
@@ -0,0 +1,591 @@
+//! Resolution of `include` and `substack` rules into a full stack
+//!
+//! A [`PamService`] only stores the rules that are written down in its own file. Rules with
+//! [`RuleContent::ServiceInclusion`] content refer to another service by name. This
+//! module follows those references and produces a [`ResolvedStack`] with all rules that are
+//! actually executed for a service and [`Domain`].
+//!
+//! Includes that form a cycle and includes that are nested deeper than [`MAX_INCLUDE_DEPTH`] are
+//! reported as a [`ResolveError`] instead of being followed.
+//!
+//! In the same way as Linux-PAM, the `other` service is used for every service name that has no
+//! configuration, both for the requested service and for included services.
+
+use std::fmt::Display;
+
+use crate::{Domain, InclusionMethod, PamConfig, PamRule, PamService, RuleContent};
+
+/// Name of the service that is used when a service has no configuration
+const OTHER_SERVICE: &str = "other";
+
+/// Maximum number of nested services that Linux-PAM follows, including the requested service
+///
+/// This is `PAM_SUBSTACK_MAX_LEVEL` in Linux-PAM, which limits both `include` and `substack`.
+pub const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// [`PamService`] that is used for a service name, as found by [`PamConfig::service`]
+#[derive(Debug, Clone, Copy)]
+pub struct ServiceLookup<'a> {
+    service: &'a PamService,
+    is_fallback: bool,
+}
+
+/// Location of a [`PamRule`] within a [`PamConfig`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RuleOrigin<'a> {
+    service: &'a str,
+    index: usize,
+}
+
+/// Fully resolved stack of rules for a service and a [`Domain`]
+///
+/// All `include` rules are replaced by the rules of the included service. Every `substack` rule
+/// becomes a nested [`ResolvedStack`] so that its scope stays visible.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResolvedStack<'a> {
+    service: &'a str,
+    domain: Domain,
+    entries: Vec<StackEntry<'a>>,
+}
+
+/// Single entry within a [`ResolvedStack`]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum StackEntry<'a> {
+    /// A module rule
+    Rule {
+        origin: RuleOrigin<'a>,
+        included_by: Vec<RuleOrigin<'a>>,
+        rule: &'a PamRule,
+    },
+    /// A `substack` rule together with the stack of the service it refers to
+    Substack {
+        origin: RuleOrigin<'a>,
+        included_by: Vec<RuleOrigin<'a>>,
+        rule: &'a PamRule,
+        stack: ResolvedStack<'a>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The service that is being resolved and the `other` service both do not exist
+    UnknownService(String),
+    /// An `include` or `substack` rule refers to a service that does not exist, and the `other`
+    /// service does not exist either
+    MissingService {
+        name: String,
+        service: String,
+        index: usize,
+    },
+    /// A service includes itself through a chain of `include` and `substack` rules
+    ///
+    /// The chain starts at the requested service and ends with the service that is included a
+    /// second time.
+    IncludeCycle(Vec<String>),
+    /// The services are nested deeper than the maximum depth
+    ///
+    /// The chain starts at the requested service and ends with the service that would exceed the
+    /// depth.
+    DepthLimit {
+        chain: Vec<String>,
+        max_depth: usize,
+    },
+}
+
+impl<'a> ServiceLookup<'a> {
+    /// Get the service that is used
+    pub fn service(&self) -> &'a PamService {
+        self.service
+    }
+
+    /// Get whether the `other` service is used, because the requested service does not exist
+    pub fn is_fallback(&self) -> bool {
+        self.is_fallback
+    }
+}
+
+impl<'a> RuleOrigin<'a> {
+    /// Get the name of the service that contains the rule
+    pub fn service(&self) -> &'a str {
+        self.service
+    }
+
+    /// Get the index of the rule within [`PamService::rules`]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<'a> ResolvedStack<'a> {
+    /// Get the name of the service this stack was resolved for
+    ///
+    /// This is `other` if the requested service does not exist.
+    pub fn service(&self) -> &'a str {
+        self.service
+    }
+
+    /// Get the [`Domain`] this stack was resolved for
+    pub fn domain(&self) -> Domain {
+        self.domain
+    }
+
+    /// Get the entries of this stack in execution order
+    pub fn entries(&self) -> &[StackEntry<'a>] {
+        &self.entries
+    }
+}
+
+impl<'a> StackEntry<'a> {
+    /// Get where the rule of this entry was written down
+    pub fn origin(&self) -> RuleOrigin<'a> {
+        match self {
+            StackEntry::Rule { origin, .. } | StackEntry::Substack { origin, .. } => *origin,
+        }
+    }
+
+    /// Get the `include` rules through which this entry ended up in its stack
+    ///
+    /// The first rule is written in the service of the stack, every next rule is written in the
+    /// service that is included by the previous one. This is empty for rules that are written in
+    /// the service of the stack itself.
+    pub fn included_by(&self) -> &[RuleOrigin<'a>] {
+        match self {
+            StackEntry::Rule { included_by, .. } | StackEntry::Substack { included_by, .. } => {
+                included_by
+            }
+        }
+    }
+
+    /// Get the rule of this entry
+    pub fn rule(&self) -> &'a PamRule {
+        match self {
+            StackEntry::Rule { rule, .. } | StackEntry::Substack { rule, .. } => rule,
+        }
+    }
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveError::UnknownService(name) => write!(f, "unknown service `{}`", name),
+            ResolveError::MissingService {
+                name,
+                service,
+                index,
+            } => write!(
+                f,
+                "service `{}` included by rule {} of `{}` does not exist",
+                name, index, service
+            ),
+            ResolveError::IncludeCycle(chain) => {
+                write!(f, "include cycle `{}`", chain.join(" -> "))
+            }
+            ResolveError::DepthLimit { chain, max_depth } => write!(
+                f,
+                "includes are nested deeper than {} levels in `{}`",
+                max_depth,
+                chain.join(" -> ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+impl PamConfig {
+    /// Find the [`PamService`] that is used for a service name
+    ///
+    /// If there is no service with the given `name`, the `other` service is used in the same way
+    /// as Linux-PAM does. [`ServiceLookup::is_fallback`] tells whether that happened. Returns
+    /// `None` if neither of the two services exists.
+    pub fn service(&self, name: &str) -> Option<ServiceLookup<'_>> {
+        if let Some(service) = self.find_service(name) {
+            return Some(ServiceLookup {
+                service,
+                is_fallback: false,
+            });
+        }
+
+        self.find_service(OTHER_SERVICE)
+            .map(|service| ServiceLookup {
+                service,
+                is_fallback: true,
+            })
+    }
+
+    /// Resolve the stack of rules for a service and a [`Domain`]
+    ///
+    /// This follows all `include` and `substack` rules across the services in this
+    /// [`PamConfig`]. Only rules of the given `domain` are taken into account, which is the same
+    /// as what Linux-PAM does. Services without configuration fall back to the `other` service,
+    /// see [`PamConfig::service`].
+    ///
+    /// Services are nested at most [`MAX_INCLUDE_DEPTH`] levels deep, which is the limit of
+    /// Linux-PAM. Use [`PamConfig::resolve_with_max_depth`] for a different limit.
+    pub fn resolve(
+        &self,
+        service: &str,
+        domain: Domain,
+    ) -> Result<ResolvedStack<'_>, ResolveError> {
+        self.resolve_with_max_depth(service, domain, MAX_INCLUDE_DEPTH)
+    }
+
+    /// Resolve the stack of rules for a service and a [`Domain`] with a maximum nesting depth
+    ///
+    /// This is the same as [`PamConfig::resolve`], except that services may be nested at most
+    /// `max_depth` levels deep. The requested service itself counts as the first level.
+    pub fn resolve_with_max_depth(
+        &self,
+        service: &str,
+        domain: Domain,
+        max_depth: usize,
+    ) -> Result<ResolvedStack<'_>, ResolveError> {
+        let service = self
+            .service(service)
+            .ok_or_else(|| ResolveError::UnknownService(service.to_string()))?
+            .service();
+
+        let mut entries = Vec::new();
+        let mut chain = Vec::new();
+        let mut included_by = Vec::new();
+        self.resolve_into(
+            service,
+            domain,
+            max_depth,
+            &mut chain,
+            &mut included_by,
+            &mut entries,
+        )?;
+
+        Ok(ResolvedStack {
+            service: service.name(),
+            domain,
+            entries,
+        })
+    }
+
+    fn find_service(&self, name: &str) -> Option<&PamService> {
+        self.services.iter().find(|service| service.name() == name)
+    }
+
+    fn resolve_into<'a>(
+        &'a self,
+        service: &'a PamService,
+        domain: Domain,
+        max_depth: usize,
+        chain: &mut Vec<&'a str>,
+        included_by: &mut Vec<RuleOrigin<'a>>,
+        entries: &mut Vec<StackEntry<'a>>,
+    ) -> Result<(), ResolveError> {
+        let chain_with = |chain: &[&str]| -> Vec<String> {
+            chain
+                .iter()
+                .chain([&service.name()])
+                .map(|name| name.to_string())
+                .collect()
+        };
+
+        if chain.contains(&service.name()) {
+            return Err(ResolveError::IncludeCycle(chain_with(chain)));
+        }
+        if chain.len() >= max_depth {
+            return Err(ResolveError::DepthLimit {
+                chain: chain_with(chain),
+                max_depth,
+            });
+        }
+
+        chain.push(service.name());
+
+        for (index, rule) in service.rules().iter().enumerate() {
+            // An `@include` directive has no domain and includes all domains
+            if rule
+                .domain()
+                .is_some_and(|rule_domain| rule_domain != domain)
+            {
+                continue;
+            }
+
+            let origin = RuleOrigin {
+                service: service.name(),
+                index,
+            };
+
+            match rule.content() {
+                RuleContent::ServiceInclusion {
+                    method: InclusionMethod::Include | InclusionMethod::IncludeDirective,
+                    service: name,
+                } => {
+                    let included = self.included_service(name, origin)?;
+
+                    included_by.push(origin);
+                    self.resolve_into(included, domain, max_depth, chain, included_by, entries)?;
+                    included_by.pop();
+                }
+                RuleContent::ServiceInclusion {
+                    method: InclusionMethod::Substack,
+                    service: name,
+                } => {
+                    let included = self.included_service(name, origin)?;
+
+                    // A substack is a stack on its own, so its rules are not included by anything
+                    let mut substack = Vec::new();
+                    self.resolve_into(
+                        included,
+                        domain,
+                        max_depth,
+                        chain,
+                        &mut Vec::new(),
+                        &mut substack,
+                    )?;
+
+                    entries.push(StackEntry::Substack {
+                        origin,
+                        included_by: included_by.clone(),
+                        rule,
+                        stack: ResolvedStack {
+                            service: included.name(),
+                            domain,
+                            entries: substack,
+                        },
+                    });
+                }
+                RuleContent::Entry { .. } => entries.push(StackEntry::Rule {
+                    origin,
+                    included_by: included_by.clone(),
+                    rule,
+                }),
+            }
+        }
+
+        chain.pop();
+
+        Ok(())
+    }
+
+    fn included_service(
+        &self,
+        name: &str,
+        origin: RuleOrigin,
+    ) -> Result<&PamService, ResolveError> {
+        self.service(name)
+            .map(|lookup| lookup.service())
+            .ok_or_else(|| ResolveError::MissingService {
+                name: name.to_string(),
+                service: origin.service.to_string(),
+                index: origin.index,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const PAM_CONF: &str = r#"
+login   auth        requisite   pam_nologin.so
+login   auth        include     system-auth
+login   account     include     system-auth
+login   session     substack    system-auth
+login   session     optional    pam_motd.so
+system-auth auth        required    pam_env.so
+system-auth auth        sufficient  pam_unix.so
+system-auth auth        required    pam_deny.so
+system-auth account     required    pam_unix.so
+system-auth session     required    pam_limits.so
+system-auth session     required    pam_unix.so
+broken  auth        include     missing
+debian  @include    system-auth
+debian  session     optional    pam_motd.so
+"#;
+
+    fn modules(entries: &[StackEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| match entry.rule().content() {
+                RuleContent::ServiceInclusion { service, .. } => service.clone(),
+                RuleContent::Entry { module_path, .. } => module_path.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn include() {
+        let config = PamConfig::from_str(PAM_CONF).unwrap();
+        let stack = config.resolve("login", Domain::Auth).unwrap();
+
+        assert_eq!(stack.service(), "login");
+        assert_eq!(
+            modules(stack.entries()),
+            ["pam_nologin.so", "pam_env.so", "pam_unix.so", "pam_deny.so"]
+        );
+
+        let origins: Vec<(&str, usize)> = stack
+            .entries()
+            .iter()
+            .map(|entry| (entry.origin().service(), entry.origin().index()))
+            .collect();
+        assert_eq!(
+            origins,
+            [
+                ("login", 0),
+                ("system-auth", 0),
+                ("system-auth", 1),
+                ("system-auth", 2)
+            ]
+        );
+
+        assert!(stack.entries()[0].included_by().is_empty());
+        assert_eq!(
+            stack.entries()[1].included_by(),
+            [RuleOrigin {
+                service: "login",
+                index: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn substack() {
+        let config = PamConfig::from_str(PAM_CONF).unwrap();
+        let stack = config.resolve("login", Domain::Session).unwrap();
+
+        assert_eq!(modules(stack.entries()), ["system-auth", "pam_motd.so"]);
+
+        let StackEntry::Substack {
+            stack: substack, ..
+        } = &stack.entries()[0]
+        else {
+            panic!("Expected a substack");
+        };
+        assert_eq!(substack.service(), "system-auth");
+        assert_eq!(
+            modules(substack.entries()),
+            ["pam_limits.so", "pam_unix.so"]
+        );
+    }
+
+    #[test]
+    fn include_directive() {
+        let config = PamConfig::from_str(PAM_CONF).unwrap();
+
+        let stack = config.resolve("debian", Domain::Auth).unwrap();
+        assert_eq!(
+            modules(stack.entries()),
+            ["pam_env.so", "pam_unix.so", "pam_deny.so"]
+        );
+
+        let stack = config.resolve("debian", Domain::Session).unwrap();
+        assert_eq!(
+            modules(stack.entries()),
+            ["pam_limits.so", "pam_unix.so", "pam_motd.so"]
+        );
+        assert_eq!(stack.entries()[0].origin().service(), "system-auth");
+    }
+
+    #[test]
+    fn other_fallback() {
+        let pam_conf = format!(
+            "{}other auth required pam_warn.so\nother auth required pam_deny.so\n",
+            PAM_CONF
+        );
+        let config = PamConfig::from_str(&pam_conf).unwrap();
+
+        let lookup = config.service("login").unwrap();
+        assert_eq!(lookup.service().name(), "login");
+        assert!(!lookup.is_fallback());
+
+        let lookup = config.service("cockpit").unwrap();
+        assert_eq!(lookup.service().name(), "other");
+        assert!(lookup.is_fallback());
+
+        let stack = config.resolve("cockpit", Domain::Auth).unwrap();
+        assert_eq!(stack.service(), "other");
+        assert_eq!(modules(stack.entries()), ["pam_warn.so", "pam_deny.so"]);
+
+        let stack = config.resolve("broken", Domain::Auth).unwrap();
+        assert_eq!(modules(stack.entries()), ["pam_warn.so", "pam_deny.so"]);
+        assert_eq!(stack.entries()[0].origin().service(), "other");
+
+        let config = PamConfig::from_str(PAM_CONF).unwrap();
+        assert!(config.service("cockpit").is_none());
+    }
+
+    #[test]
+    fn include_cycle() {
+        let pam_conf = r#"
+login       auth    include     common-auth
+common-auth auth    include     system-auth
+system-auth auth    required    pam_env.so
+system-auth auth    substack    common-auth
+"#;
+        let config = PamConfig::from_str(pam_conf).unwrap();
+
+        let error = config.resolve("login", Domain::Auth).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "include cycle `login -> common-auth -> system-auth -> common-auth`"
+        );
+
+        // Including the same service twice next to each other is not a cycle
+        let pam_conf = r#"
+login       auth    include     system-auth
+login       auth    include     system-auth
+system-auth auth    required    pam_env.so
+"#;
+        let config = PamConfig::from_str(pam_conf).unwrap();
+        let stack = config.resolve("login", Domain::Auth).unwrap();
+        assert_eq!(modules(stack.entries()), ["pam_env.so", "pam_env.so"]);
+    }
+
+    #[test]
+    fn depth_limit() {
+        let config = PamConfig::from_str(PAM_CONF).unwrap();
+
+        assert!(config
+            .resolve_with_max_depth("login", Domain::Auth, 2)
+            .is_ok());
+        assert_eq!(
+            config
+                .resolve_with_max_depth("login", Domain::Auth, 1)
+                .unwrap_err(),
+            ResolveError::DepthLimit {
+                chain: vec!["login".to_string(), "system-auth".to_string()],
+                max_depth: 1,
+            }
+        );
+
+        let pam_conf: String = (0..=MAX_INCLUDE_DEPTH)
+            .map(|i| format!("level{} auth include level{}\n", i, i + 1))
+            .collect();
+        let config = PamConfig::from_str(&pam_conf).unwrap();
+        let error = config.resolve("level0", Domain::Auth).unwrap_err();
+        assert!(
+            matches!(error, ResolveError::DepthLimit { chain, .. } if chain.len() == MAX_INCLUDE_DEPTH + 1)
+        );
+    }
+
+    #[test]
+    fn missing_service() {
+        let config = PamConfig::from_str(PAM_CONF).unwrap();
+
+        assert_eq!(
+            config.resolve("sshd", Domain::Auth).unwrap_err(),
+            ResolveError::UnknownService("sshd".to_string())
+        );
+        assert_eq!(
+            config.resolve("broken", Domain::Auth).unwrap_err(),
+            ResolveError::MissingService {
+                name: "missing".to_string(),
+                service: "broken".to_string(),
+                index: 0,
+            }
+        );
+    }
+}
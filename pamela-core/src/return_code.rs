@@ -95,3 +95,41 @@ pub enum ReturnCode {
     /// Conversation is incomplete
     Incomplete,
 }
+
+impl ReturnCode {
+    /// All return codes in the order of their numeric value in Linux-PAM
+    pub(crate) const ALL: [ReturnCode; 32] = [
+        ReturnCode::Success,
+        ReturnCode::OpenError,
+        ReturnCode::SymbolError,
+        ReturnCode::ServiceError,
+        ReturnCode::SystemError,
+        ReturnCode::BufError,
+        ReturnCode::PermissionDenied,
+        ReturnCode::AuthenticationError,
+        ReturnCode::CredentialsInsufficient,
+        ReturnCode::AuthInfoUnavailable,
+        ReturnCode::UserUnknown,
+        ReturnCode::MaximumTriesReached,
+        ReturnCode::NewAuthTokenRequired,
+        ReturnCode::AccountExpired,
+        ReturnCode::SessionError,
+        ReturnCode::CredentialsUnavailable,
+        ReturnCode::CredentialsExpired,
+        ReturnCode::CredentialsError,
+        ReturnCode::NoModuleData,
+        ReturnCode::ConversationError,
+        ReturnCode::AuthTokenManipulationError,
+        ReturnCode::AuthTokenRecoverError,
+        ReturnCode::AuthTokenLockBusy,
+        ReturnCode::AuthTokenDisableAging,
+        ReturnCode::TryAgain,
+        ReturnCode::Ignore,
+        ReturnCode::Abort,
+        ReturnCode::AuthTokenExpired,
+        ReturnCode::ModuleUnknown,
+        ReturnCode::BadItem,
+        ReturnCode::ConversationAgain,
+        ReturnCode::Incomplete,
+    ];
+}
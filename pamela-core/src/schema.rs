@@ -0,0 +1,789 @@
+//! Typed argument schemas for well-known modules
+//!
+//! Modules silently ignore arguments that they do not know, so a typo such as `try_frist_pass`
+//! is easy to miss. A [`ModuleSchema`] describes the arguments that a module accepts, which is
+//! used to validate the arguments of a rule and to parse them into typed structs such as
+//! [`PamUnixArguments`].
+
+use std::fmt::Display;
+use std::path::Path;
+
+use crate::{ModuleArgument, PamRule};
+
+/// Maximum edit distance for an unknown argument to be reported as a typo of a known one
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Type of the value of a module argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    /// The argument is given without a value, e.g. `nullok`
+    Flag,
+    /// The argument has a whole number as value, e.g. `deny=3`
+    Integer,
+    /// The argument has a boolean as value, e.g. `debug=no`. Without a value it is `true`.
+    Boolean,
+    /// The argument has `0` or `1` as value, e.g. `readenv=0`
+    Switch,
+    /// The argument has an arbitrary non-empty value, e.g. `conf=/etc/limits.conf`
+    Text,
+}
+
+/// Parsed value of a module argument
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgumentValue {
+    Flag,
+    Integer(i64),
+    Boolean(bool),
+    Text(String),
+}
+
+/// Single argument that a module accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgumentSchema {
+    name: &'static str,
+    kind: ArgumentKind,
+}
+
+/// Arguments that a module accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleSchema {
+    module: &'static str,
+    arguments: &'static [ArgumentSchema],
+    positional: bool,
+}
+
+/// Collection of [`ModuleSchema`]s that rules are validated against
+///
+/// The [`Default`] contains the schemas of the well-known modules in this module.
+#[derive(Debug, Clone)]
+pub struct SchemaRegistry {
+    schemas: Vec<ModuleSchema>,
+}
+
+/// Mistake in the arguments of a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgumentError {
+    module: &'static str,
+    index: usize,
+    argument: String,
+    kind: ArgumentErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgumentErrorKind {
+    /// The module does not know the argument, which may be a typo of `suggestion`
+    Unknown { suggestion: Option<&'static str> },
+    /// The argument needs a value, but none was given
+    MissingValue,
+    /// The argument is a flag, but a value was given
+    UnexpectedValue,
+    /// The value of the argument does not have the expected type
+    WrongType(ArgumentKind),
+}
+
+/// Arguments of a module parsed into a struct
+pub trait TypedArguments: Sized {
+    /// Schema of the arguments of the module
+    const SCHEMA: ModuleSchema;
+
+    /// Parse module arguments, failing with all mistakes that are found
+    fn from_arguments(arguments: &[ModuleArgument]) -> Result<Self, Vec<ArgumentError>>;
+}
+
+/// Argument that passed validation
+enum ParsedArgument<'a> {
+    Known(&'static str, ArgumentValue),
+    Positional(&'a ModuleArgument),
+}
+
+impl ArgumentKind {
+    fn parse(self, value: Option<&str>) -> Result<ArgumentValue, ArgumentErrorKind> {
+        use ArgumentKind::*;
+
+        match (self, value) {
+            (Flag, None) => Ok(ArgumentValue::Flag),
+            (Flag, Some(_)) => Err(ArgumentErrorKind::UnexpectedValue),
+            (Boolean, None) => Ok(ArgumentValue::Boolean(true)),
+            (_, None | Some("")) => Err(ArgumentErrorKind::MissingValue),
+            (Integer, Some(value)) => value
+                .parse()
+                .map(ArgumentValue::Integer)
+                .map_err(|_| ArgumentErrorKind::WrongType(self)),
+            (Boolean, Some(value)) => match value {
+                "1" | "yes" | "true" | "on" => Ok(ArgumentValue::Boolean(true)),
+                "0" | "no" | "false" | "off" => Ok(ArgumentValue::Boolean(false)),
+                _ => Err(ArgumentErrorKind::WrongType(self)),
+            },
+            (Switch, Some("0")) => Ok(ArgumentValue::Boolean(false)),
+            (Switch, Some("1")) => Ok(ArgumentValue::Boolean(true)),
+            (Switch, Some(_)) => Err(ArgumentErrorKind::WrongType(self)),
+            (Text, Some(value)) => Ok(ArgumentValue::Text(value.to_string())),
+        }
+    }
+}
+
+impl ArgumentSchema {
+    pub const fn new(name: &'static str, kind: ArgumentKind) -> Self {
+        Self { name, kind }
+    }
+
+    /// Get the name of the argument, i.e. the part before the `=`
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the type of the value of the argument
+    pub fn kind(&self) -> ArgumentKind {
+        self.kind
+    }
+}
+
+impl ModuleSchema {
+    /// Create a schema for the module with file name `module`, e.g. `pam_unix.so`
+    ///
+    /// A module with `positional` arguments gets all arguments that are not in `arguments`
+    /// passed on as is, such as the conditions of `pam_succeed_if.so`.
+    pub const fn new(
+        module: &'static str,
+        arguments: &'static [ArgumentSchema],
+        positional: bool,
+    ) -> Self {
+        Self {
+            module,
+            arguments,
+            positional,
+        }
+    }
+
+    /// Get the file name of the module
+    pub fn module(&self) -> &'static str {
+        self.module
+    }
+
+    /// Get the named arguments that the module accepts
+    pub fn arguments(&self) -> &'static [ArgumentSchema] {
+        self.arguments
+    }
+
+    /// Get the schema of a named argument
+    pub fn argument(&self, name: &str) -> Option<&'static ArgumentSchema> {
+        self.arguments.iter().find(|argument| argument.name == name)
+    }
+
+    /// Get whether the module accepts positional arguments besides the named arguments
+    pub fn is_positional(&self) -> bool {
+        self.positional
+    }
+
+    /// Check module arguments against this schema
+    pub fn validate(&self, arguments: &[ModuleArgument]) -> Vec<ArgumentError> {
+        match self.parse(arguments) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors,
+        }
+    }
+
+    fn parse<'a>(
+        &self,
+        arguments: &'a [ModuleArgument],
+    ) -> Result<Vec<ParsedArgument<'a>>, Vec<ArgumentError>> {
+        let mut parsed = Vec::with_capacity(arguments.len());
+        let mut errors = Vec::new();
+
+        for (index, argument) in arguments.iter().enumerate() {
+            let (name, value) = match argument {
+                ModuleArgument::KeyValue { key, value } => (key.as_str(), Some(value.as_str())),
+                ModuleArgument::Set(key) => (key.as_str(), None),
+            };
+
+            let error = |kind| ArgumentError {
+                module: self.module,
+                index,
+                argument: name.to_string(),
+                kind,
+            };
+
+            let Some(schema) = self.argument(name) else {
+                match self.positional {
+                    true => parsed.push(ParsedArgument::Positional(argument)),
+                    false => errors.push(error(ArgumentErrorKind::Unknown {
+                        suggestion: self.suggestion(name),
+                    })),
+                }
+                continue;
+            };
+
+            match schema.kind.parse(value) {
+                Ok(value) => parsed.push(ParsedArgument::Known(schema.name, value)),
+                Err(kind) => errors.push(error(kind)),
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(parsed),
+            false => Err(errors),
+        }
+    }
+
+    /// Find the known argument that is closest to an unknown `name`
+    fn suggestion(&self, name: &str) -> Option<&'static str> {
+        self.arguments
+            .iter()
+            .map(|argument| (edit_distance(name, argument.name), argument.name))
+            .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, name)| name)
+    }
+}
+
+impl SchemaRegistry {
+    /// Create a registry without any schemas
+    pub fn new() -> Self {
+        Self {
+            schemas: Vec::new(),
+        }
+    }
+
+    /// Add a schema, replacing an earlier schema for the same module
+    pub fn register(&mut self, schema: ModuleSchema) {
+        self.schemas
+            .retain(|existing| existing.module != schema.module);
+        self.schemas.push(schema);
+    }
+
+    /// Get the schemas in this registry
+    pub fn schemas(&self) -> &[ModuleSchema] {
+        &self.schemas
+    }
+
+    /// Get the schema for a module by its path or file name
+    pub fn get(&self, module: impl AsRef<Path>) -> Option<&ModuleSchema> {
+        let name = module.as_ref().file_name()?;
+        self.schemas.iter().find(|schema| schema.module == name)
+    }
+
+    /// Check the arguments of a rule
+    ///
+    /// Rules that include another service or that call a module without a schema have no errors.
+    pub fn validate(&self, rule: &PamRule) -> Vec<ArgumentError> {
+        let Some(schema) = rule.module_path().and_then(|path| self.get(path.path())) else {
+            return Vec::new();
+        };
+
+        schema.validate(rule.module_arguments())
+    }
+}
+
+impl Default for SchemaRegistry {
+    fn default() -> Self {
+        Self {
+            schemas: WELL_KNOWN_SCHEMAS.to_vec(),
+        }
+    }
+}
+
+impl ArgumentError {
+    /// Get the file name of the module that the argument is passed to
+    pub fn module(&self) -> &'static str {
+        self.module
+    }
+
+    /// Get the position of the argument in the module arguments
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the name of the argument, i.e. the part before the `=`
+    pub fn argument(&self) -> &str {
+        &self.argument
+    }
+
+    pub fn kind(&self) -> &ArgumentErrorKind {
+        &self.kind
+    }
+}
+
+impl PamRule {
+    /// Parse the module arguments into the typed arguments of a module
+    ///
+    /// This is `None` if the rule does not call the module of `T`.
+    pub fn typed_arguments<T: TypedArguments>(&self) -> Option<Result<T, Vec<ArgumentError>>> {
+        let path = self.module_path()?.path();
+        if path.file_name()? != T::SCHEMA.module {
+            return None;
+        }
+
+        Some(T::from_arguments(self.module_arguments()))
+    }
+}
+
+impl From<ArgumentKind> for &'static str {
+    fn from(value: ArgumentKind) -> Self {
+        match value {
+            ArgumentKind::Flag => "flag",
+            ArgumentKind::Integer => "integer",
+            ArgumentKind::Boolean => "boolean",
+            ArgumentKind::Switch => "switch",
+            ArgumentKind::Text => "text",
+        }
+    }
+}
+
+impl Display for ArgumentKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <&'static str>::from(*self).fmt(f)
+    }
+}
+
+impl Display for ArgumentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ArgumentErrorKind::Unknown { suggestion } => {
+                write!(
+                    f,
+                    "unknown argument `{}` for `{}`",
+                    self.argument, self.module
+                )?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean `{}`?", suggestion)?;
+                }
+                Ok(())
+            }
+            ArgumentErrorKind::MissingValue => write!(
+                f,
+                "argument `{}` of `{}` needs a value",
+                self.argument, self.module
+            ),
+            ArgumentErrorKind::UnexpectedValue => write!(
+                f,
+                "argument `{}` of `{}` does not take a value",
+                self.argument, self.module
+            ),
+            ArgumentErrorKind::WrongType(kind) => write!(
+                f,
+                "argument `{}` of `{}` needs a value of type {}",
+                self.argument, self.module, kind
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArgumentError {}
+
+/// Field of a typed arguments struct that is set from a validated [`ArgumentValue`]
+trait ArgumentField {
+    fn assign(&mut self, value: ArgumentValue);
+}
+
+impl ArgumentField for bool {
+    fn assign(&mut self, _: ArgumentValue) {
+        *self = true;
+    }
+}
+
+impl ArgumentField for Option<i64> {
+    fn assign(&mut self, value: ArgumentValue) {
+        if let ArgumentValue::Integer(value) = value {
+            *self = Some(value);
+        }
+    }
+}
+
+impl ArgumentField for Option<bool> {
+    fn assign(&mut self, value: ArgumentValue) {
+        if let ArgumentValue::Boolean(value) = value {
+            *self = Some(value);
+        }
+    }
+}
+
+impl ArgumentField for Option<String> {
+    fn assign(&mut self, value: ArgumentValue) {
+        if let ArgumentValue::Text(value) = value {
+            *self = Some(value);
+        }
+    }
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+macro_rules! field_type {
+    (Flag) => { bool };
+    (Integer) => { Option<i64> };
+    (Boolean) => { Option<bool> };
+    (Switch) => { Option<bool> };
+    (Text) => { Option<String> };
+}
+
+macro_rules! module_schemas {
+    ($(
+        $(#[doc = $doc:literal])*
+        $name:ident($module:literal $(, $positional:ident)?) {
+            $($argument:ident: $kind:ident),* $(,)?
+        }
+    )*) => {
+        $(
+        $(#[doc = $doc])*
+        #[derive(Debug, Clone, Default, PartialEq, Eq)]
+        pub struct $name {
+            $(pub $argument: field_type!($kind),)*
+            $(pub $positional: Vec<String>,)?
+        }
+
+        impl TypedArguments for $name {
+            const SCHEMA: ModuleSchema = ModuleSchema::new(
+                $module,
+                &[$(ArgumentSchema::new(stringify!($argument), ArgumentKind::$kind)),*],
+                false $(|| !stringify!($positional).is_empty())?,
+            );
+
+            fn from_arguments(arguments: &[ModuleArgument]) -> Result<Self, Vec<ArgumentError>> {
+                #[allow(unused_mut)]
+                let mut typed = Self::default();
+
+                for argument in Self::SCHEMA.parse(arguments)? {
+                    match argument {
+                        $(ParsedArgument::Known(stringify!($argument), value) => {
+                            typed.$argument.assign(value)
+                        })*
+                        ParsedArgument::Known(..) => unreachable!(),
+                        #[allow(unused_variables)]
+                        ParsedArgument::Positional(argument) => {
+                            $(typed.$positional.push(argument.to_string());)?
+                        }
+                    }
+                }
+
+                Ok(typed)
+            }
+        }
+        )*
+
+        /// Schemas of all well-known modules
+        const WELL_KNOWN_SCHEMAS: &[ModuleSchema] = &[$($name::SCHEMA),*];
+    };
+}
+
+module_schemas! {
+    /// Arguments of `pam_unix.so`
+    PamUnixArguments("pam_unix.so") {
+        debug: Flag,
+        audit: Flag,
+        quiet: Flag,
+        nullok: Flag,
+        nullresetok: Flag,
+        try_first_pass: Flag,
+        use_first_pass: Flag,
+        nodelay: Flag,
+        use_authtok: Flag,
+        authtok_type: Text,
+        not_set_pass: Flag,
+        nis: Flag,
+        shadow: Flag,
+        md5: Flag,
+        bigcrypt: Flag,
+        sha256: Flag,
+        sha512: Flag,
+        blowfish: Flag,
+        gost_yescrypt: Flag,
+        yescrypt: Flag,
+        rounds: Integer,
+        broken_shadow: Flag,
+        minlen: Integer,
+        no_pass_expiry: Flag,
+        obscure: Flag,
+        remember: Integer,
+    }
+
+    /// Arguments of `pam_faillock.so`
+    PamFaillockArguments("pam_faillock.so") {
+        preauth: Flag,
+        authfail: Flag,
+        authsucc: Flag,
+        conf: Text,
+        dir: Text,
+        audit: Flag,
+        silent: Flag,
+        no_log_info: Flag,
+        local_users_only: Flag,
+        nodelay: Flag,
+        deny: Integer,
+        fail_interval: Integer,
+        unlock_time: Text,
+        even_deny_root: Flag,
+        root_unlock_time: Integer,
+        admin_group: Text,
+    }
+
+    /// Arguments of `pam_pwquality.so`
+    PamPwqualityArguments("pam_pwquality.so") {
+        debug: Flag,
+        authtok_type: Text,
+        retry: Integer,
+        difok: Integer,
+        minlen: Integer,
+        dcredit: Integer,
+        ucredit: Integer,
+        lcredit: Integer,
+        ocredit: Integer,
+        minclass: Integer,
+        maxrepeat: Integer,
+        maxsequence: Integer,
+        maxclassrepeat: Integer,
+        gecoscheck: Integer,
+        dictcheck: Integer,
+        usercheck: Integer,
+        usersubstr: Integer,
+        enforcing: Integer,
+        badwords: Text,
+        dictpath: Text,
+        retry_once: Flag,
+        enforce_for_root: Flag,
+        local_users_only: Flag,
+        use_authtok: Flag,
+    }
+
+    /// Arguments of `pam_env.so`
+    PamEnvArguments("pam_env.so") {
+        debug: Flag,
+        conffile: Text,
+        envfile: Text,
+        readenv: Switch,
+        user_envfile: Text,
+        user_readenv: Switch,
+    }
+
+    /// Arguments of `pam_limits.so`
+    PamLimitsArguments("pam_limits.so") {
+        conf: Text,
+        debug: Flag,
+        set_all: Flag,
+        utmp_early: Flag,
+        noaudit: Flag,
+    }
+
+    /// Arguments of `pam_succeed_if.so`
+    ///
+    /// Everything besides the flags is part of the conditions, e.g. `uid`, `>=` and `1000`.
+    PamSucceedIfArguments("pam_succeed_if.so", conditions) {
+        debug: Flag,
+        use_uid: Flag,
+        quiet: Flag,
+        quiet_fail: Flag,
+        quiet_success: Flag,
+        audit: Flag,
+    }
+
+    /// Arguments of `pam_systemd_home.so`
+    PamSystemdHomeArguments("pam_systemd_home.so") {
+        debug: Boolean,
+        suspend: Boolean,
+    }
+
+    /// Arguments of `pam_deny.so`
+    PamDenyArguments("pam_deny.so") {}
+
+    /// Arguments of `pam_permit.so`
+    PamPermitArguments("pam_permit.so") {}
+
+    /// Arguments of `pam_rootok.so`
+    PamRootokArguments("pam_rootok.so") {
+        debug: Flag,
+    }
+
+    /// Arguments of `pam_nologin.so`
+    PamNologinArguments("pam_nologin.so") {
+        file: Text,
+        successok: Flag,
+    }
+
+    /// Arguments of `pam_faildelay.so`
+    PamFaildelayArguments("pam_faildelay.so") {
+        debug: Flag,
+        delay: Integer,
+    }
+
+    /// Arguments of `pam_wheel.so`
+    PamWheelArguments("pam_wheel.so") {
+        debug: Flag,
+        deny: Flag,
+        group: Text,
+        root_only: Flag,
+        trust: Flag,
+        use_uid: Flag,
+    }
+
+    /// Arguments of `pam_time.so`
+    PamTimeArguments("pam_time.so") {
+        conffile: Text,
+        debug: Flag,
+        noaudit: Flag,
+    }
+
+    /// Arguments of `pam_access.so`
+    PamAccessArguments("pam_access.so") {
+        accessfile: Text,
+        debug: Flag,
+        noaudit: Flag,
+        nodefgroup: Flag,
+        quiet_log: Flag,
+        fieldsep: Text,
+        listsep: Text,
+    }
+
+    /// Arguments of `pam_keyinit.so`
+    PamKeyinitArguments("pam_keyinit.so") {
+        debug: Flag,
+        force: Flag,
+        revoke: Flag,
+    }
+
+    /// Arguments of `pam_loginuid.so`
+    PamLoginuidArguments("pam_loginuid.so") {
+        require_auditd: Flag,
+    }
+
+    /// Arguments of `pam_umask.so`
+    PamUmaskArguments("pam_umask.so") {
+        debug: Flag,
+        usergroups: Flag,
+        nousergroups: Flag,
+        umask: Text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn arguments(s: &str) -> Vec<ModuleArgument> {
+        s.split_whitespace()
+            .map(|argument| ModuleArgument::from_str(argument).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn validate() {
+        let registry = SchemaRegistry::default();
+        let rule = |s: &str| PamRule::separated_iter(s).next().unwrap().unwrap();
+
+        assert!(registry
+            .validate(&rule("auth required pam_unix.so try_first_pass nullok"))
+            .is_empty());
+        assert!(registry
+            .validate(&rule("auth required pam_unknown.so whatever"))
+            .is_empty());
+        assert!(registry
+            .validate(&rule("auth include common-auth"))
+            .is_empty());
+
+        let errors = registry.validate(&rule(
+            "auth required /usr/lib/security/pam_unix.so try_frist_pass nullok=1 rounds",
+        ));
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            errors[0].kind(),
+            &ArgumentErrorKind::Unknown {
+                suggestion: Some("try_first_pass")
+            }
+        );
+        assert_eq!(errors[1].kind(), &ArgumentErrorKind::UnexpectedValue);
+        assert_eq!(errors[2].kind(), &ArgumentErrorKind::MissingValue);
+        assert_eq!(errors[2].index(), 2);
+        assert_eq!(
+            errors[0].to_string(),
+            "unknown argument `try_frist_pass` for `pam_unix.so`, did you mean `try_first_pass`?"
+        );
+
+        let errors = registry.validate(&rule("auth required pam_faillock.so deny=three"));
+        assert_eq!(
+            errors[0].kind(),
+            &ArgumentErrorKind::WrongType(ArgumentKind::Integer)
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "argument `deny` of `pam_faillock.so` needs a value of type integer"
+        );
+    }
+
+    #[test]
+    fn typed() {
+        let faillock =
+            PamFaillockArguments::from_arguments(&arguments("authfail deny=3 unlock_time=never"))
+                .unwrap();
+        assert_eq!(
+            faillock,
+            PamFaillockArguments {
+                authfail: true,
+                deny: Some(3),
+                unlock_time: Some("never".to_string()),
+                ..Default::default()
+            }
+        );
+
+        let env = PamEnvArguments::from_arguments(&arguments("readenv=0 user_readenv=1")).unwrap();
+        assert_eq!(env.readenv, Some(false));
+        assert_eq!(env.user_readenv, Some(true));
+
+        let errors =
+            PamEnvArguments::from_arguments(&arguments("readenv user_readenv=yes")).unwrap_err();
+        assert_eq!(errors[0].kind(), &ArgumentErrorKind::MissingValue);
+        assert_eq!(
+            errors[1].kind(),
+            &ArgumentErrorKind::WrongType(ArgumentKind::Switch)
+        );
+
+        let succeed_if =
+            PamSucceedIfArguments::from_arguments(&arguments("uid >= 1000 quiet_success")).unwrap();
+        assert!(succeed_if.quiet_success);
+        assert_eq!(succeed_if.conditions, ["uid", ">=", "1000"]);
+
+        assert!(PamDenyArguments::from_arguments(&arguments("debug")).is_err());
+    }
+
+    #[test]
+    fn typed_rule() {
+        let rule = PamRule::separated_iter("password sufficient pam_unix.so sha512 remember=5")
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let unix = rule.typed_arguments::<PamUnixArguments>().unwrap().unwrap();
+        assert!(unix.sha512);
+        assert_eq!(unix.remember, Some(5));
+        assert!(rule.typed_arguments::<PamFaillockArguments>().is_none());
+    }
+
+    #[test]
+    fn registry() {
+        const ARGUMENTS: &[ArgumentSchema] = &[ArgumentSchema::new("debug", ArgumentKind::Flag)];
+
+        let mut registry = SchemaRegistry::new();
+        assert!(registry.get("pam_custom.so").is_none());
+
+        registry.register(ModuleSchema::new("pam_custom.so", &[], false));
+        registry.register(ModuleSchema::new("pam_custom.so", ARGUMENTS, false));
+
+        assert_eq!(registry.schemas().len(), 1);
+        let schema = registry.get("/lib/security/pam_custom.so").unwrap();
+        assert_eq!(schema.argument("debug").unwrap().kind(), ArgumentKind::Flag);
+        assert!(schema.validate(&arguments("debug")).is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{PamConfig, PamConfigError, PamService, PAM_CONF_PATH, PAM_D_PATH};
+
+const PAM_VENDOR_D_PATHS: [&str; 2] = ["/usr/lib/pam.d", "/usr/etc/pam.d"];
+
+/// Layered set of locations that are searched for the PAM configuration
+///
+/// The configuration directories are layers. A service file in an earlier directory masks a
+/// service file with the same name in all later directories. If none of the directories exist,
+/// the packed configuration file is used instead.
+///
+/// The [`Default`] search path is the one used by Linux-PAM: `/etc/pam.d`, followed by the vendor
+/// directories `/usr/lib/pam.d` and `/usr/etc/pam.d`, with `/etc/pam.conf` as the fallback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchPath {
+    dirs: Vec<PathBuf>,
+    file: PathBuf,
+}
+
+impl SearchPath {
+    /// Create a [`SearchPath`] from configuration directories ordered from the highest to the
+    /// lowest priority, and a packed configuration file
+    pub fn new(
+        dirs: impl IntoIterator<Item = impl Into<PathBuf>>,
+        file: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            dirs: dirs.into_iter().map(Into::into).collect(),
+            file: file.into(),
+        }
+    }
+
+    /// Create a [`SearchPath`] for an alternate configuration directory
+    ///
+    /// This is the directory that would be passed to `pam_start_confdir`. It takes the place of
+    /// `/etc/pam.d` and the vendor directories are not searched. If the directory does not exist,
+    /// Linux-PAM still falls back to `/etc/pam.conf` and so does this.
+    pub fn confdir(confdir: impl Into<PathBuf>) -> Self {
+        Self::new([confdir.into()], PAM_CONF_PATH)
+    }
+
+    /// Get the configuration directories ordered from the highest to the lowest priority
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Get the packed configuration file
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    /// Find the service file for every service over all layers
+    ///
+    /// Returns `None` if none of the configuration directories exist. Directories within the
+    /// configuration directories are skipped. Entries that cannot be used as a service file, such
+    /// as a file name that is not UTF-8, are returned next to the service files, so that they do
+    /// not hide the other services.
+    pub(crate) fn service_files(&self) -> Result<Option<ServiceFiles>, PamConfigError> {
+        let mut files = BTreeMap::new();
+        let mut errors = Vec::new();
+        let mut has_dir = false;
+
+        // Walk the layers from the lowest to the highest priority, so that the higher layers
+        // overwrite the lower ones.
+        for dir in self.dirs.iter().rev() {
+            if !dir.is_dir() {
+                continue;
+            }
+            has_dir = true;
+
+            for dir_entry in fs::read_dir(dir)? {
+                let path = match dir_entry {
+                    Ok(dir_entry) => dir_entry.path(),
+                    Err(err) => {
+                        errors.push(PamConfigError::Io(err));
+                        continue;
+                    }
+                };
+                if path.is_dir() {
+                    continue;
+                }
+
+                let Some(name) = path.file_name() else {
+                    errors.push(PamConfigError::NotAFilename);
+                    continue;
+                };
+                let Some(name) = name.to_str() else {
+                    let err = PamConfigError::NonUTF8Filename;
+                    errors.push(PamConfigError::InFile(path, Box::new(err)));
+                    continue;
+                };
+
+                files.insert(name.to_string(), path);
+            }
+        }
+
+        Ok(has_dir.then_some(ServiceFiles { files, errors }))
+    }
+}
+
+/// Service files over all layers of a [`SearchPath`]
+pub(crate) struct ServiceFiles {
+    /// Path of the service file of every service by name
+    pub(crate) files: BTreeMap<String, PathBuf>,
+    /// Directory entries that are not used as a service file
+    pub(crate) errors: Vec<PamConfigError>,
+}
+
+impl Default for SearchPath {
+    fn default() -> Self {
+        let dirs = std::iter::once(PAM_D_PATH).chain(PAM_VENDOR_D_PATHS);
+        Self::new(dirs, PAM_CONF_PATH)
+    }
+}
+
+impl PamConfig {
+    /// Read a [`PamConfig`] from the locations in a [`SearchPath`]
+    ///
+    /// If any of the configuration directories exist, the service files of all directories are
+    /// used, where a service file masks the service files with the same name in lower priority
+    /// directories. [`PamService::layer`] tells which directory a service was read from. If none
+    /// of the directories exist, the packed configuration file is used.
+    pub fn from_search_path(search_path: &SearchPath) -> Result<PamConfig, PamConfigError> {
+        // From the Linux-PAM manual page:
+        //
+        // > This dynamic configuration is set by the contents of the single Linux-PAM
+        // > configuration file /etc/pam.conf. Alternatively, the configuration can be set by
+        // > individual configuration files located in the /etc/pam.d/ directory. The presence of
+        // > this directory will cause Linux-PAM to ignore /etc/pam.conf.
+
+        let Some(ServiceFiles { files, errors }) = search_path.service_files()? else {
+            return Self::from_file(search_path.file());
+        };
+        if let Some(err) = errors.into_iter().next() {
+            return Err(err);
+        }
+
+        let services = files
+            .into_values()
+            .map(PamService::from_file)
+            .collect::<Result<Vec<PamService>, PamConfigError>>()?;
+
+        Ok(PamConfig { services })
+    }
+
+    /// Read a [`PamConfig`] from an alternate configuration directory
+    ///
+    /// This is the configuration that is used by `pam_start_confdir` and allows reading a
+    /// configuration outside of `/etc`. See [`SearchPath::confdir`] for the details.
+    pub fn from_confdir(confdir: impl AsRef<Path>) -> Result<PamConfig, PamConfigError> {
+        Self::from_search_path(&SearchPath::confdir(confdir.as_ref()))
+    }
+
+    /// Leniently read a [`PamConfig`] from the locations in a [`SearchPath`]
+    ///
+    /// This uses the same files as [`PamConfig::from_search_path`], but does not stop at the first
+    /// error in the same way as [`PamConfig::from_dir_lenient`]. A directory entry that cannot be
+    /// used as a service file is recorded as an error as well. Only failing to list the
+    /// configuration directories or to read the packed configuration file is an error.
+    pub fn from_search_path_lenient(
+        search_path: &SearchPath,
+    ) -> Result<(PamConfig, Vec<PamConfigError>), PamConfigError> {
+        let Some(ServiceFiles { files, mut errors }) = search_path.service_files()? else {
+            let (config, errors) = Self::from_file_lenient(search_path.file())?;
+            let errors = errors.into_iter().map(PamConfigError::Syntax).collect();
+            return Ok((config, errors));
+        };
+
+        let mut services = Vec::new();
+
+        for path in files.into_values() {
+            match PamService::from_file_lenient(&path) {
+                Ok((service, syntax_errors)) => {
+                    services.push(service);
+                    errors.extend(syntax_errors.into_iter().map(PamConfigError::Syntax));
+                }
+                Err(err) => errors.push(PamConfigError::InFile(path, Box::new(err))),
+            }
+        }
+
+        Ok((PamConfig { services }, errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers() {
+        let root = std::env::temp_dir().join(format!("pamela-layers-{}", std::process::id()));
+        let etc = root.join("etc/pam.d");
+        let vendor = root.join("usr/lib/pam.d");
+        fs::create_dir_all(&etc).unwrap();
+        fs::create_dir_all(&vendor).unwrap();
+
+        fs::write(etc.join("login"), "auth required pam_env.so\n").unwrap();
+        fs::write(vendor.join("login"), "auth required pam_deny.so\n").unwrap();
+        fs::write(vendor.join("sshd"), "auth required pam_unix.so\n").unwrap();
+
+        let search_path = SearchPath::new(
+            [etc.clone(), vendor.clone(), root.join("usr/etc/pam.d")],
+            root.join("etc/pam.conf"),
+        );
+        let config = PamConfig::from_search_path(&search_path);
+
+        fs::remove_dir_all(&root).unwrap();
+        let config = config.unwrap();
+
+        let services: Vec<(&str, Option<&Path>, String)> = config
+            .services()
+            .iter()
+            .map(|service| {
+                let module = service.rules()[0].module_path().unwrap().to_string();
+                (service.name(), service.layer(), module)
+            })
+            .collect();
+
+        assert_eq!(
+            services,
+            [
+                ("login", Some(etc.as_path()), "pam_env.so".to_string()),
+                ("sshd", Some(vendor.as_path()), "pam_unix.so".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn odd_file_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let root = std::env::temp_dir().join(format!("pamela-odd-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("login"), "auth required pam_env.so\n").unwrap();
+        let odd = root.join(OsStr::from_bytes(b"login\xff"));
+        fs::write(&odd, "auth required pam_deny.so\n").unwrap();
+
+        let search_path = SearchPath::new([&root], root.join("pam.conf"));
+        let strict = PamConfig::from_search_path(&search_path);
+        let lenient = PamConfig::from_search_path_lenient(&search_path);
+
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(matches!(strict, Err(PamConfigError::InFile(path, _)) if path == odd));
+
+        let (config, errors) = lenient.unwrap();
+        assert_eq!(config.services().len(), 1);
+        assert_eq!(config.services()[0].name(), "login");
+        assert!(matches!(
+            &errors[..],
+            [PamConfigError::InFile(path, err)]
+                if *path == odd && matches!(**err, PamConfigError::NonUTF8Filename)
+        ));
+    }
+
+    #[test]
+    fn packed_fallback() {
+        let root = std::env::temp_dir().join(format!("pamela-fallback-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("pam.conf"),
+            "login auth required pam_env.so\nlogin auth requird pam_unix.so\n",
+        )
+        .unwrap();
+
+        let search_path = SearchPath::new([root.join("pam.d")], root.join("pam.conf"));
+        let strict = PamConfig::from_search_path(&search_path);
+        let lenient = PamConfig::from_search_path_lenient(&search_path);
+
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(matches!(strict, Err(PamConfigError::Syntax(_))));
+
+        let (config, errors) = lenient.unwrap();
+        assert_eq!(config.services()[0].name(), "login");
+        assert_eq!(config.services()[0].layer(), None);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn confdir() {
+        let root = std::env::temp_dir().join(format!("pamela-confdir-{}", std::process::id()));
+        let confdir = root.join("pam.d");
+
+        // Linux-PAM falls back to `/etc/pam.conf` rather than a file next to the directory
+        let search_path = SearchPath::confdir(&confdir);
+        assert_eq!(search_path.dirs(), std::slice::from_ref(&confdir));
+        assert_eq!(search_path.file(), Path::new(PAM_CONF_PATH));
+
+        fs::create_dir_all(&confdir).unwrap();
+        fs::write(confdir.join("login"), "auth required pam_env.so\n").unwrap();
+        fs::write(confdir.join("sshd"), "auth required pam_env.so\n").unwrap();
+
+        let separated = PamConfig::from_confdir(&confdir).map(|config| config.services().len());
+
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(separated.unwrap(), 2);
+    }
+}
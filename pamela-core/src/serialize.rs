@@ -0,0 +1,350 @@
+//! Serialization of the configuration model with [`serde`]
+//!
+//! This module is only available with the `serde` feature. The schema below is stable: fields
+//! may be added in a new [`EXPORT_SCHEMA_VERSION`], but existing fields keep their name and
+//! meaning.
+//!
+//! A [`PamConfig`] is written as its list of services, and [`PamConfig::export`] additionally
+//! writes the resolved stacks of all services. As JSON, an export looks like this:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "services": [
+//!     {
+//!       "name": "login",
+//!       "layer": "/etc/pam.d",
+//!       "rules": [
+//!         {
+//!           "span": {
+//!             "path": "/etc/pam.d/login",
+//!             "range": { "start": 0, "end": 29 },
+//!             "line": 1,
+//!             "column": 1
+//!           },
+//!           "is_logging_enabled": true,
+//!           "domain": "auth",
+//!           "kind": "service_inclusion",
+//!           "method": "include",
+//!           "service": "common-auth"
+//!         }
+//!       ],
+//!       "stacks": [
+//!         {
+//!           "stack": {
+//!             "service": "login",
+//!             "domain": "auth",
+//!             "entries": [
+//!               {
+//!                 "kind": "rule",
+//!                 "origin": { "service": "common-auth", "index": 0 },
+//!                 "included_by": [{ "service": "login", "index": 0 }],
+//!                 "rule": {
+//!                   "span": { "...": "..." },
+//!                   "is_logging_enabled": true,
+//!                   "domain": "auth",
+//!                   "kind": "entry",
+//!                   "control": "[success=1 default=ignore]",
+//!                   "module_path": "pam_unix.so",
+//!                   "module_arguments": ["nullok", "remember=5"]
+//!                 }
+//!               }
+//!             ]
+//!           }
+//!         },
+//!         { "error": { "domain": "account", "message": "..." } }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! - A rule is either of `kind` `entry` or `service_inclusion`. The `method` of an inclusion is
+//!   `include`, `substack` or `@include`, and its `domain` is `null` for `@include`.
+//! - Controls, domains, module paths and return codes are written as they appear in the
+//!   configuration. A module argument is written as `key=value` or `key` without any escaping.
+//! - A `span` points into the source that the rule was parsed from. The `range` is in bytes,
+//!   `line` and `column` are 1-based.
+//! - A stack entry is either of `kind` `rule` or `substack`. A `substack` entry additionally has
+//!   the `stack` of the service that it refers to. `included_by` lists the `include` rules that
+//!   the entry was expanded through, outermost first.
+//!
+//! Only the [`PamConfig`] and the types it consists of can be deserialized.
+
+use std::str::FromStr;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    Action, Control, Domain, InclusionMethod, ModuleArgument, ModulePath, PamConfig, PamRule,
+    ResolvedStack, ReturnCode, Selection, SelectionItem, Value, INCLUDE_DIRECTIVE,
+};
+
+/// Version of the schema written by [`PamConfig::export`]
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Serializable view of a [`PamConfig`] together with the resolved stacks of its services
+#[derive(Debug, Serialize)]
+pub struct PamConfigExport<'a> {
+    version: u32,
+    services: Vec<ServiceExport<'a>>,
+}
+
+/// Serializable view of a [`PamService`](crate::PamService) in a [`PamConfigExport`]
+#[derive(Debug, Serialize)]
+pub struct ServiceExport<'a> {
+    name: &'a str,
+    layer: Option<&'a std::path::Path>,
+    rules: &'a [PamRule],
+    stacks: Vec<StackExport<'a>>,
+}
+
+/// Result of resolving the stack of one [`Domain`] of a service
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StackExport<'a> {
+    Stack(ResolvedStack<'a>),
+    Error { domain: Domain, message: String },
+}
+
+impl PamConfig {
+    /// Create a serializable view of this configuration including the resolved stacks
+    ///
+    /// Every service gets a stack for every [`Domain`]. A stack that cannot be resolved, e.g.
+    /// because of an include cycle, is written as an error instead.
+    pub fn export(&self) -> PamConfigExport<'_> {
+        let domains = [
+            Domain::Auth,
+            Domain::Account,
+            Domain::Password,
+            Domain::Session,
+        ];
+
+        let services = self
+            .services
+            .iter()
+            .map(|service| ServiceExport {
+                name: service.name(),
+                layer: service.layer(),
+                rules: service.rules(),
+                stacks: domains
+                    .into_iter()
+                    .map(|domain| match self.resolve(service.name(), domain) {
+                        Ok(stack) => StackExport::Stack(stack),
+                        Err(err) => StackExport::Error {
+                            domain,
+                            message: err.to_string(),
+                        },
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        PamConfigExport {
+            version: EXPORT_SCHEMA_VERSION,
+            services,
+        }
+    }
+}
+
+/// Implement serde for types that are written in the same way as in the configuration
+macro_rules! serde_as_str {
+    ($($ty:ty => $expecting:literal),* $(,)?) => {
+        $(
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let s = String::deserialize(deserializer)?;
+                <$ty>::from_str(&s)
+                    .map_err(|_| D::Error::custom(format!("invalid {} `{}`", $expecting, s)))
+            }
+        }
+        )*
+    };
+}
+
+serde_as_str! {
+    Control => "control",
+    Selection => "selection",
+    SelectionItem => "selection item",
+    Value => "return value",
+    Action => "action",
+    Domain => "domain",
+    ModulePath => "module path",
+}
+
+impl Serialize for InclusionMethod {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for InclusionMethod {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+
+        // `InclusionMethod::from_str` only parses the controls, not the `@include` directive
+        if s == INCLUDE_DIRECTIVE {
+            return Ok(InclusionMethod::IncludeDirective);
+        }
+        InclusionMethod::from_str(&s)
+            .map_err(|_| D::Error::custom(format!("invalid inclusion method `{}`", s)))
+    }
+}
+
+impl Serialize for ReturnCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Value::ReturnCode(*self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ReturnCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::ReturnCode(return_code) => Ok(return_code),
+            Value::Default => Err(D::Error::custom("invalid return code `default`")),
+        }
+    }
+}
+
+impl Serialize for ModuleArgument {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ModuleArgument::KeyValue { key, value } => {
+                serializer.collect_str(&format_args!("{}={}", key, value))
+            }
+            ModuleArgument::Set(key) => serializer.serialize_str(key),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ModuleArgument {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+
+        // NOTE: Arguments are written without escaping, so they may contain whitespace.
+        match s.split_once('=') {
+            Some(("", _)) => Err(D::Error::custom(format!("invalid module argument `{}`", s))),
+            Some((key, value)) => Ok(ModuleArgument::KeyValue {
+                key: key.to_string(),
+                value: value.to_string(),
+            }),
+            None => Ok(ModuleArgument::Set(s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{PamService, RuleContent};
+
+    const PAM_CONF: &str = r#"
+login       auth    include                     common-auth
+login       account requisite                   pam_nologin.so
+common-auth auth    [success=1 default=ignore]  pam_unix.so     nullok [foo=a b]
+common-auth auth    requisite                   pam_deny.so
+loop        auth    include                     loop
+"#;
+
+    #[test]
+    fn rule() {
+        let config = PamConfig::from_str(PAM_CONF).unwrap();
+        let rule = &config.service("common-auth").unwrap().service().rules()[0];
+
+        assert_eq!(
+            serde_json::to_value(rule).unwrap(),
+            json!({
+                "span": {
+                    "path": null,
+                    "range": { "start": 124, "end": 204 },
+                    "line": 4,
+                    "column": 1,
+                },
+                "is_logging_enabled": true,
+                "domain": "auth",
+                "kind": "entry",
+                "control": "[success=1 default=ignore]",
+                "module_path": "pam_unix.so",
+                "module_arguments": ["nullok", "foo=a b"],
+            })
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = PamConfig::from_str(PAM_CONF).unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        let deserialized: PamConfig = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), json);
+
+        let rule = &deserialized
+            .service("common-auth")
+            .unwrap()
+            .service()
+            .rules()[0];
+        let RuleContent::Entry {
+            module_arguments, ..
+        } = rule.content()
+        else {
+            panic!("Expected an entry");
+        };
+        assert!(matches!(
+            &module_arguments[1],
+            ModuleArgument::KeyValue { key, value } if key == "foo" && value == "a b"
+        ));
+
+        let rules = PamService::from_str("@include common-auth\n").unwrap();
+        let json = serde_json::to_string(&rules).unwrap();
+        assert!(json.contains(r#""method":"@include""#));
+        let deserialized: Vec<PamRule> = serde_json::from_str(&json).unwrap();
+        assert!(deserialized[0].is_include_directive());
+        assert!(serde_json::from_value::<InclusionMethod>(json!("@includes")).is_err());
+
+        assert!(serde_json::from_value::<Control>(json!("requird")).is_err());
+        assert!(serde_json::from_value::<ReturnCode>(json!("default")).is_err());
+        assert_eq!(
+            serde_json::from_value::<ReturnCode>(json!("auth_err")).unwrap(),
+            ReturnCode::AuthenticationError
+        );
+    }
+
+    #[test]
+    fn export() {
+        let config = PamConfig::from_str(PAM_CONF).unwrap();
+        let export = serde_json::to_value(config.export()).unwrap();
+
+        assert_eq!(export["version"], EXPORT_SCHEMA_VERSION);
+
+        let login = &export["services"][1];
+        assert_eq!(login["name"], "login");
+        assert_eq!(login["rules"].as_array().unwrap().len(), 2);
+
+        let auth = &login["stacks"][0]["stack"];
+        assert_eq!(auth["domain"], "auth");
+        assert_eq!(auth["entries"].as_array().unwrap().len(), 2);
+        assert_eq!(auth["entries"][0]["kind"], "rule");
+        assert_eq!(
+            auth["entries"][0]["origin"],
+            json!({ "service": "common-auth", "index": 0 })
+        );
+        assert_eq!(
+            auth["entries"][0]["included_by"],
+            json!([{ "service": "login", "index": 0 }])
+        );
+        assert_eq!(auth["entries"][1]["rule"]["module_path"], "pam_deny.so");
+
+        let cycle = &export["services"][2]["stacks"][0]["error"];
+        assert_eq!(cycle["domain"], "auth");
+        assert_eq!(cycle["message"], "include cycle `loop -> loop`");
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt::{Display, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Location of a piece of a PAM configuration source
+///
+/// The `range` is a byte range into the original source, so it includes any backslash line
+/// continuations. The `line` and `column` are both 1-based and point at the start of the range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    path: Option<Arc<Path>>,
+    range: Range<usize>,
+    line: usize,
+    column: usize,
+}
+
+impl Span {
+    pub(crate) fn new(
+        path: Option<Arc<Path>>,
+        range: Range<usize>,
+        line: usize,
+        column: usize,
+    ) -> Self {
+        Self {
+            path,
+            range,
+            line,
+            column,
+        }
+    }
+
+    /// Create a span for something that was not parsed from a source
+    pub(crate) fn detached() -> Self {
+        Self::new(None, 0..0, 1, 1)
+    }
+
+    /// Get the path of the file this span points into, if the source was read from a file
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Get the byte range within the source
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Get the 1-based line number of the start of the span
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Get the 1-based column, counted in characters, of the start of the span
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Render a rustc-style diagnostic pointing at this span
+    ///
+    /// The `source` has to be the same source that the span was created from.
+    ///
+    /// ```text
+    /// error: unknown action `xyz`
+    ///  --> /etc/pam.d/login:3:12
+    ///   |
+    /// 3 | auth       [default=xyz]    pam_unix.so
+    ///   |            ^^^^^^^^^^^^^
+    /// ```
+    pub fn render(&self, source: &str, message: impl Display) -> String {
+        let start = self.range.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line = &source[line_start..line_end];
+
+        let end = self.range.end.clamp(start, line_end);
+        let carets = source[start..end].chars().count().max(1);
+
+        // Keep tabs in the padding so the carets line up with the source line
+        let padding: String = source[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        let mut rendered = String::new();
+        let _ = writeln!(rendered, "error: {}", message);
+        let _ = writeln!(rendered, "{}--> {}", gutter, self);
+        let _ = writeln!(rendered, "{} |", gutter);
+        let _ = writeln!(rendered, "{} | {}", line_number, line);
+        let _ = write!(rendered, "{} | {}{}", gutter, padding, "^".repeat(carets));
+
+        rendered
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}:{}:{}", path.display(), self.line, self.column),
+            None => write!(f, "{}:{}", self.line, self.column),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let source = "auth required pam_env.so\nauth\t[default=xyz] pam_unix.so\n";
+        let span = Span::new(Some(Arc::from(Path::new("login"))), 30..43, 2, 6);
+
+        assert_eq!(
+            span.render(source, "unknown action `xyz`"),
+            "error: unknown action `xyz`\n \
+             --> login:2:6\n  \
+             |\n\
+             2 | auth\t[default=xyz] pam_unix.so\n  \
+             |     \t^^^^^^^^^^^^^"
+        );
+    }
+}
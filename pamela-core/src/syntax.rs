@@ -0,0 +1,438 @@
+//! Lossless syntax tree of a service file
+//!
+//! The parsers in the rest of this crate throw away everything that does not influence the
+//! behaviour of PAM: comments, blank lines, the `#%PAM-1.0` header, the alignment of the columns
+//! and the exact placement of line continuations. The [`SyntaxTree`] keeps all of that, so that
+//! writing it back out reproduces the source byte-for-byte.
+
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::control::ControlParseError;
+use crate::parsing::{take_string, till_end_of_line};
+use crate::{
+    Control, Domain, InclusionMethod, ModuleArgument, PamConfigSyntaxError, PamRule, PamService,
+    INCLUDE_DIRECTIVE,
+};
+
+/// Lossless syntax tree of a service file in the separated configuration format
+///
+/// # Examples
+///
+/// ```
+/// use pamela::SyntaxTree;
+///
+/// let source = "#%PAM-1.0\n\n# Use the system defaults\nauth    include    system-auth\n";
+/// let tree = SyntaxTree::parse(source);
+///
+/// assert_eq!(tree.to_string(), source);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxTree {
+    nodes: Vec<SyntaxNode>,
+}
+
+/// Single logical line of a [`SyntaxTree`]
+///
+/// Every node owns its text including the line feed that ends it and all escaped line feeds
+/// within it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxNode {
+    /// The `#%PAM-1.0` header line
+    Header(String),
+    /// A line that starts with `#`
+    Comment(String),
+    /// A line that only contains spaces and tabs
+    Blank(String),
+    /// A line that contains a rule
+    Rule(RuleNode),
+}
+
+/// Rule line of a [`SyntaxTree`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleNode {
+    tokens: Vec<SyntaxToken>,
+}
+
+/// Piece of text within a [`RuleNode`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxToken {
+    kind: TokenKind,
+    text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Spaces, tabs and escaped line feeds between the other tokens
+    Whitespace,
+    /// The `@include` keyword of a Debian-style `@include` directive
+    Directive,
+    /// The domain including the optional `-` prefix
+    Domain,
+    Control,
+    ModulePath,
+    ModuleArgument,
+    /// The line feed that ends the rule
+    LineFeed,
+}
+
+const PAM_HEADER: &str = "#%PAM-";
+
+impl SyntaxTree {
+    /// Parse a service file into a [`SyntaxTree`]
+    ///
+    /// This never fails. Lines with syntax errors still become a [`SyntaxNode::Rule`] and only
+    /// report their error when looking at them through [`RuleNode::rule`].
+    pub fn parse(source: &str) -> Self {
+        let mut nodes = Vec::new();
+
+        let mut s = source;
+        while !s.is_empty() {
+            let (line, leftover, _) = till_end_of_line(s);
+            let text = &s[..leftover];
+            s = &s[leftover..];
+
+            let trimmed = line.trim();
+            let node = if trimmed.is_empty() {
+                SyntaxNode::Blank(text.to_string())
+            } else if trimmed.starts_with(PAM_HEADER) && nodes.is_empty() {
+                SyntaxNode::Header(text.to_string())
+            } else if trimmed.starts_with('#') {
+                SyntaxNode::Comment(text.to_string())
+            } else {
+                SyntaxNode::Rule(RuleNode::lex(text))
+            };
+
+            nodes.push(node);
+        }
+
+        Self { nodes }
+    }
+
+    /// Get all the nodes in source order
+    pub fn nodes(&self) -> &[SyntaxNode] {
+        &self.nodes
+    }
+
+    pub(crate) fn nodes_mut(&mut self) -> &mut Vec<SyntaxNode> {
+        &mut self.nodes
+    }
+
+    /// Get all the rule nodes in source order
+    pub fn rule_nodes(&self) -> impl Iterator<Item = &RuleNode> {
+        self.nodes.iter().filter_map(|node| match node {
+            SyntaxNode::Rule(rule) => Some(rule),
+            _ => None,
+        })
+    }
+
+    /// Get the typed [`PamRule`]s of this tree
+    ///
+    /// This gives the same result as parsing the original source with [`PamService::from_str`].
+    pub fn rules(&self) -> Result<Vec<PamRule>, PamConfigSyntaxError> {
+        PamService::from_str(&self.to_string())
+    }
+}
+
+impl SyntaxNode {
+    /// Get the exact source text of this node
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            SyntaxNode::Header(text) | SyntaxNode::Comment(text) | SyntaxNode::Blank(text) => {
+                Cow::Borrowed(text)
+            }
+            SyntaxNode::Rule(rule) => Cow::Owned(rule.to_string()),
+        }
+    }
+}
+
+impl RuleNode {
+    pub(crate) fn lex(text: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut s = text;
+        let mut word_count = 0;
+
+        while !s.is_empty() {
+            let (kind, length) = if s.starts_with('\n') {
+                (TokenKind::LineFeed, 1)
+            } else if let Some(length) = whitespace_length(s) {
+                (TokenKind::Whitespace, length)
+            } else {
+                let length = word_length(s);
+                if word_count == 0 && &s[..length] == INCLUDE_DIRECTIVE {
+                    // The service name of an `@include` directive takes the place of the module
+                    word_count = 2;
+                    tokens.push(SyntaxToken {
+                        kind: TokenKind::Directive,
+                        text: s[..length].to_string(),
+                    });
+                    s = &s[length..];
+                    continue;
+                }
+
+                let kind = match word_count {
+                    0 => TokenKind::Domain,
+                    1 => TokenKind::Control,
+                    2 => TokenKind::ModulePath,
+                    _ => TokenKind::ModuleArgument,
+                };
+                word_count += 1;
+
+                (kind, length)
+            };
+
+            tokens.push(SyntaxToken {
+                kind,
+                text: s[..length].to_string(),
+            });
+            s = &s[length..];
+        }
+
+        Self { tokens }
+    }
+
+    /// Get all the tokens of this rule, including the whitespace between them
+    pub fn tokens(&self) -> &[SyntaxToken] {
+        &self.tokens
+    }
+
+    pub(crate) fn tokens_mut(&mut self) -> &mut Vec<SyntaxToken> {
+        &mut self.tokens
+    }
+
+    /// Get the first token of a [`TokenKind`]
+    pub fn token(&self, kind: TokenKind) -> Option<&SyntaxToken> {
+        self.tokens.iter().find(|token| token.kind == kind)
+    }
+
+    /// Get the typed [`PamRule`] of this node
+    ///
+    /// The [`Span`](crate::Span) of the rule and of any error is relative to this node.
+    pub fn rule(&self) -> Result<PamRule, PamConfigSyntaxError> {
+        let text = self.to_string();
+        let mut rules = PamRule::separated_iter(&text);
+        let rule = rules.next().expect("A rule node always contains a rule");
+        debug_assert!(rules.next().is_none());
+        rule
+    }
+
+    /// Get the typed [`Domain`] of this node and whether logging is enabled
+    pub fn domain(&self) -> Option<Result<(Domain, bool), ()>> {
+        let value = self.token(TokenKind::Domain)?.value();
+        Some(match value.strip_prefix('-') {
+            Some(domain) => Domain::from_str(domain).map(|domain| (domain, false)),
+            None => Domain::from_str(&value).map(|domain| (domain, true)),
+        })
+    }
+
+    /// Get the typed [`Control`] of this node
+    ///
+    /// This is `None` for a rule that includes another service.
+    pub fn control(&self) -> Option<Result<Control, ControlParseError>> {
+        let value = self.token(TokenKind::Control)?.value();
+        if InclusionMethod::from_str(&value).is_ok() {
+            return None;
+        }
+
+        Some(Control::from_str(&value))
+    }
+
+    /// Get the typed [`ModuleArgument`]s of this node
+    pub fn module_arguments(&self) -> impl Iterator<Item = Result<ModuleArgument, ()>> + '_ {
+        self.tokens
+            .iter()
+            .filter(|token| token.kind == TokenKind::ModuleArgument)
+            .map(|token| {
+                let value = token.value();
+                let (value, _) = take_string(&value).ok_or(())?;
+                ModuleArgument::from_str(&value)
+            })
+    }
+}
+
+impl SyntaxToken {
+    pub(crate) fn new(kind: TokenKind, text: String) -> Self {
+        Self { kind, text }
+    }
+
+    /// Get the kind of token
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    /// Get the exact source text of this token
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub(crate) fn set_text(&mut self, text: String) {
+        self.text = text;
+    }
+
+    /// Get the text of this token with all escaped line feeds replaced by a space
+    pub fn value(&self) -> Cow<'_, str> {
+        let (value, _, _) = till_end_of_line(&self.text);
+        value
+    }
+}
+
+/// Length of the spaces, tabs and escaped line feeds at the start of `s`
+fn whitespace_length(s: &str) -> Option<usize> {
+    let mut length = 0;
+    loop {
+        let rest = &s[length..];
+        if rest.starts_with([' ', '\t']) {
+            length += 1;
+        } else if rest.starts_with("\\\n") {
+            length += 2;
+        } else {
+            break;
+        }
+    }
+
+    (length > 0).then_some(length)
+}
+
+/// Length of the word or bracketed string at the start of `s`
+fn word_length(s: &str) -> usize {
+    if s.starts_with('[') {
+        let mut escaped = false;
+        for (i, c) in s.char_indices().skip(1) {
+            match c {
+                ']' if !escaped => return i + 1,
+                '\n' if !escaped => return i,
+                _ => {}
+            }
+            escaped = !escaped && c == '\\';
+        }
+
+        return s.len();
+    }
+
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => return i,
+            '\\' if matches!(chars.peek(), Some((_, '\n'))) => return i,
+            _ => {}
+        }
+    }
+
+    s.len()
+}
+
+impl Display for SyntaxTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in &self.nodes {
+            match node {
+                SyntaxNode::Header(text) | SyntaxNode::Comment(text) | SyntaxNode::Blank(text) => {
+                    f.write_str(text)?
+                }
+                SyntaxNode::Rule(rule) => rule.fmt(f)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for RuleNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for token in &self.tokens {
+            f.write_str(&token.text)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSTEM_AUTH: &str = r#"#%PAM-1.0
+
+auth       required                    pam_faillock.so      preauth
+# Optionally use requisite above if you do not want to prompt for the password
+# on locked accounts.
+-auth      [success=2 default=ignore]  pam_systemd_home.so
+auth       [success=1 \
+            default=bad]               pam_unix.so          try_first_pass nullok
+auth	optional	pam_exec.so	[cmd=/bin/echo a\]b]	\
+	quiet
+@include   common-auth
+
+session    required                    pam_unix.so"#;
+
+    #[test]
+    fn lossless() {
+        macro_rules! assert_test {
+            ($s:expr) => {
+                assert_eq!(SyntaxTree::parse($s).to_string(), $s);
+            };
+        }
+
+        assert_test!("");
+        assert_test!("\n\n");
+        assert_test!("auth required pam_env.so");
+        assert_test!("auth required pam_env.so \\\n");
+        assert_test!("auth [success=1 \\\n");
+        assert_test!(SYSTEM_AUTH);
+    }
+
+    #[test]
+    fn nodes() {
+        let tree = SyntaxTree::parse(SYSTEM_AUTH);
+
+        let kinds: Vec<&str> = tree
+            .nodes()
+            .iter()
+            .map(|node| match node {
+                SyntaxNode::Header(_) => "header",
+                SyntaxNode::Comment(_) => "comment",
+                SyntaxNode::Blank(_) => "blank",
+                SyntaxNode::Rule(_) => "rule",
+            })
+            .collect();
+
+        assert_eq!(
+            kinds,
+            [
+                "header", "blank", "rule", "comment", "comment", "rule", "rule", "rule", "rule",
+                "blank", "rule"
+            ]
+        );
+    }
+
+    #[test]
+    fn typed_views() {
+        let tree = SyntaxTree::parse(SYSTEM_AUTH);
+        let rules: Vec<&RuleNode> = tree.rule_nodes().collect();
+
+        assert_eq!(rules[1].domain(), Some(Ok((Domain::Auth, false))));
+        assert_eq!(
+            rules[2].control().unwrap().unwrap().to_string(),
+            "[success=1 default=bad]"
+        );
+
+        let arguments: Vec<String> = rules[3]
+            .module_arguments()
+            .map(|argument| argument.unwrap().to_string())
+            .collect();
+        assert_eq!(arguments, ["[cmd=/bin/echo a\\]b]", "quiet"]);
+
+        assert_eq!(rules[4].domain(), None);
+        assert_eq!(
+            rules[4].token(TokenKind::ModulePath).unwrap().text(),
+            "common-auth"
+        );
+
+        let parsed = tree.rules().unwrap();
+        assert_eq!(parsed.len(), 6);
+        assert!(parsed[4].is_include_directive());
+        for (node, rule) in rules.iter().zip(parsed.iter()) {
+            assert_eq!(node.rule().unwrap().to_string(), rule.to_string());
+        }
+    }
+}
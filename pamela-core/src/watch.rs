@@ -0,0 +1,566 @@
+//! Reloading of a [`PamConfig`] when its files change
+//!
+//! A [`ConfigWatcher`] loads a [`PamConfig`] from a [`SearchPath`] and watches the configuration
+//! directories and the packed configuration file with inotify. Changes are collected until the
+//! files have been quiet for a moment, so that an editor or package manager that writes a file in
+//! several steps only causes a single reload. Only the services whose files changed are read
+//! again, and readers get the configuration as an immutable [`Arc`] snapshot.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::io::{self, PipeReader, PipeWriter};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+
+use crate::search_path::ServiceFiles;
+use crate::{PamConfig, PamConfigError, PamService, SearchPath};
+
+/// Handle to a [`PamConfig`] that is reloaded when its files change
+///
+/// The files are watched by a background thread, which stops when the watcher is dropped. If a
+/// service file cannot be read or parsed, the previous version of that service stays in place
+/// and the error is reported as a [`ReloadEvent::Failed`].
+pub struct ConfigWatcher {
+    config: Arc<RwLock<Arc<PamConfig>>>,
+    /// Closing this pipe wakes up the watcher thread and stops it
+    stop: Option<PipeWriter>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Outcome of a reload, which is passed to the callback of a [`ConfigWatcher`]
+#[derive(Debug)]
+pub enum ReloadEvent {
+    /// A new snapshot is in place, in which the services with these names were added, changed or
+    /// removed
+    ///
+    /// This is only reported if any service changed.
+    Reloaded(Vec<String>),
+    /// The configuration could not be read, so the previous version stays in place
+    Failed(PamConfigError),
+}
+
+/// What a watch descriptor is watching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Watched {
+    /// A configuration directory with service files
+    Layer,
+    /// The directory that contains the packed configuration file
+    FileParent,
+}
+
+/// Changes that were collected since the last reload
+#[derive(Debug, Default)]
+struct Changes {
+    services: BTreeSet<OsString>,
+    /// Everything has to be read again, because events were lost or the packed configuration file
+    /// changed
+    is_complete: bool,
+}
+
+/// State of the watcher thread
+struct Reloader<F> {
+    inotify: Inotify,
+    watches: HashMap<WatchDescriptor, Watched>,
+    search_path: SearchPath,
+    uses_dirs: bool,
+    debounce: Duration,
+    config: Arc<RwLock<Arc<PamConfig>>>,
+    report: F,
+}
+
+impl ConfigWatcher {
+    /// Wait for this long after the last change before reloading in [`PamConfig::watch`]
+    pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(100);
+
+    /// Load the configuration from `search_path` and reload it when its files change
+    ///
+    /// A reload happens once no file changed for `debounce`. The outcome of every reload is
+    /// passed to `report` on the watcher thread. The configuration directories have to exist
+    /// when the watcher is created to be watched, only the packed configuration file may be
+    /// created later.
+    pub fn new(
+        search_path: SearchPath,
+        debounce: Duration,
+        report: impl FnMut(ReloadEvent) + Send + 'static,
+    ) -> Result<Self, PamConfigError> {
+        let inotify = Inotify::init()?;
+        let mask = WatchMask::CLOSE_WRITE
+            | WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO;
+
+        let mut watches = HashMap::new();
+        for dir in search_path.dirs().iter().filter(|dir| dir.is_dir()) {
+            let watch = inotify.watches().add(dir, mask)?;
+            watches.insert(watch, Watched::Layer);
+        }
+        if let Some(parent) = search_path.file().parent().filter(|parent| parent.is_dir()) {
+            let watch = inotify.watches().add(parent, mask)?;
+            watches.entry(watch).or_insert(Watched::FileParent);
+        }
+
+        // The files are watched before they are loaded, so that no change in between is missed
+        let config = Arc::new(RwLock::new(Arc::new(PamConfig::from_search_path(
+            &search_path,
+        )?)));
+        let (stop_reader, stop_writer) = io::pipe()?;
+
+        let mut reloader = Reloader {
+            inotify,
+            watches,
+            uses_dirs: uses_dirs(&search_path),
+            search_path,
+            debounce,
+            config: Arc::clone(&config),
+            report,
+        };
+        let thread = thread::spawn(move || reloader.run(&stop_reader));
+
+        Ok(Self {
+            config,
+            stop: Some(stop_writer),
+            thread: Some(thread),
+        })
+    }
+
+    /// Get the current configuration
+    ///
+    /// The snapshot does not change, a reload swaps in a new one for later calls.
+    pub fn snapshot(&self) -> Arc<PamConfig> {
+        Arc::clone(&self.config.read().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl PamConfig {
+    /// Load the configuration from `search_path` and reload it when its files change
+    ///
+    /// This uses the [`ConfigWatcher::DEFAULT_DEBOUNCE`], see [`ConfigWatcher::new`] for the
+    /// details.
+    pub fn watch(
+        search_path: SearchPath,
+        report: impl FnMut(ReloadEvent) + Send + 'static,
+    ) -> Result<ConfigWatcher, PamConfigError> {
+        ConfigWatcher::new(search_path, ConfigWatcher::DEFAULT_DEBOUNCE, report)
+    }
+}
+
+impl<F: FnMut(ReloadEvent)> Reloader<F> {
+    fn run(&mut self, stop: &PipeReader) {
+        let mut buffer = [0; 4096];
+        let mut changes = Changes::default();
+        let mut last_change = None;
+
+        loop {
+            // Without pending changes, the thread sleeps until an event arrives or it is stopped
+            let timeout = last_change
+                .map(|last_change: Instant| self.debounce.saturating_sub(last_change.elapsed()));
+            let [has_events, is_stopped] = match wait([self.inotify.as_fd(), stop.as_fd()], timeout)
+            {
+                Ok(ready) => ready,
+                Err(err) => {
+                    (self.report)(ReloadEvent::Failed(err.into()));
+                    return;
+                }
+            };
+            if is_stopped {
+                return;
+            }
+
+            if has_events {
+                match self.inotify.read_events(&mut buffer) {
+                    Ok(events) => {
+                        for event in events {
+                            if self.record(&event.wd, event.mask, event.name, &mut changes) {
+                                last_change = Some(Instant::now());
+                            }
+                        }
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(err) => {
+                        (self.report)(ReloadEvent::Failed(err.into()));
+                        return;
+                    }
+                }
+            }
+
+            if last_change
+                .is_some_and(|last_change: Instant| last_change.elapsed() >= self.debounce)
+            {
+                self.reload(std::mem::take(&mut changes));
+                last_change = None;
+            }
+        }
+    }
+
+    /// Add an event to `changes` and get whether it is relevant
+    fn record(
+        &self,
+        watch: &WatchDescriptor,
+        mask: EventMask,
+        name: Option<&OsStr>,
+        changes: &mut Changes,
+    ) -> bool {
+        if mask.contains(EventMask::Q_OVERFLOW) {
+            changes.is_complete = true;
+            return true;
+        }
+
+        let (Some(watched), Some(name)) = (self.watches.get(watch), name) else {
+            return false;
+        };
+
+        match watched {
+            Watched::Layer => {
+                changes.services.insert(name.to_os_string());
+                true
+            }
+            // The packed configuration file is only read when no configuration directory exists
+            Watched::FileParent if Some(name) == self.search_path.file().file_name() => {
+                changes.is_complete |= !self.uses_dirs;
+                true
+            }
+            Watched::FileParent => false,
+        }
+    }
+
+    fn reload(&mut self, changes: Changes) {
+        // A configuration directory that appeared or disappeared switches between the service
+        // files and the packed configuration file
+        let uses_dirs = uses_dirs(&self.search_path);
+        if changes.is_complete || uses_dirs != self.uses_dirs {
+            self.uses_dirs = uses_dirs;
+            self.reload_all();
+        } else if uses_dirs && !changes.services.is_empty() {
+            self.reload_services(changes.services);
+        }
+    }
+
+    fn reload_all(&mut self) {
+        let config = match self.search_path.service_files() {
+            Ok(Some(service_files)) => Ok(self.read_service_files(service_files)),
+            Ok(None) => PamConfig::from_file(self.search_path.file()),
+            Err(err) => Err(err),
+        };
+
+        match config {
+            Ok(config) => {
+                let names = changed_services(&self.current(), &config);
+                if !names.is_empty() {
+                    self.swap(config);
+                    (self.report)(ReloadEvent::Reloaded(names));
+                }
+            }
+            Err(err) => (self.report)(ReloadEvent::Failed(err)),
+        }
+    }
+
+    /// Read every service file, keeping the previous version of a service that cannot be read
+    fn read_service_files(&mut self, service_files: ServiceFiles) -> PamConfig {
+        let current = self.current();
+        let mut services = Vec::new();
+
+        for err in service_files.errors {
+            (self.report)(ReloadEvent::Failed(err));
+        }
+
+        for (name, path) in service_files.files {
+            match PamService::from_file(&path) {
+                Ok(service) => services.push(service),
+                Err(err) => {
+                    let err = PamConfigError::InFile(path, Box::new(err));
+                    (self.report)(ReloadEvent::Failed(err));
+                    let previous = current
+                        .services()
+                        .iter()
+                        .find(|service| service.name() == name);
+                    services.extend(previous.cloned());
+                }
+            }
+        }
+
+        PamConfig { services }
+    }
+
+    fn reload_services(&mut self, names: BTreeSet<OsString>) {
+        let current = self.current();
+        let mut services = current.services().to_vec();
+        let mut changed = Vec::new();
+
+        for name in names {
+            let Some(name) = name.to_str() else {
+                let path = self.layer_path(&name);
+                let err = PamConfigError::InFile(path, Box::new(PamConfigError::NonUTF8Filename));
+                (self.report)(ReloadEvent::Failed(err));
+                continue;
+            };
+
+            let position = services.binary_search_by(|service| service.name().cmp(name));
+            let service = match self.service_file(name) {
+                Some(path) => match PamService::from_file(&path) {
+                    Ok(service) => Some(service),
+                    Err(err) => {
+                        let err = PamConfigError::InFile(path, Box::new(err));
+                        (self.report)(ReloadEvent::Failed(err));
+                        continue;
+                    }
+                },
+                None => None,
+            };
+
+            match (position, service) {
+                (Ok(position), Some(service)) => services[position] = service,
+                (Err(position), Some(service)) => services.insert(position, service),
+                (Ok(position), None) => {
+                    services.remove(position);
+                }
+                (Err(_), None) => continue,
+            }
+            changed.push(name.to_string());
+        }
+
+        if !changed.is_empty() {
+            self.swap(PamConfig { services });
+            (self.report)(ReloadEvent::Reloaded(changed));
+        }
+    }
+
+    /// Find the service file of a single service over all layers
+    fn service_file(&self, name: &str) -> Option<PathBuf> {
+        self.search_path
+            .dirs()
+            .iter()
+            .map(|dir| dir.join(name))
+            .find(|path| path.exists() && !path.is_dir())
+    }
+
+    /// Get a path for a file name in the highest priority configuration directory
+    fn layer_path(&self, name: &OsStr) -> PathBuf {
+        self.search_path
+            .dirs()
+            .first()
+            .map_or_else(|| PathBuf::from(name), |dir| dir.join(name))
+    }
+
+    fn current(&self) -> Arc<PamConfig> {
+        Arc::clone(&self.config.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn swap(&self, config: PamConfig) {
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+    }
+}
+
+/// Wait until the file descriptors can be read or the timeout passed, and get which of them can
+/// be read
+///
+/// Without a timeout, this waits until one of them can be read.
+fn wait(fds: [BorrowedFd<'_>; 2], timeout: Option<Duration>) -> io::Result<[bool; 2]> {
+    let mut poll_fds = fds.map(|fd| libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    });
+    // Rounding up keeps the thread from waking up just before the timeout
+    let timeout = timeout.map_or(-1, |timeout| {
+        i32::try_from(timeout.as_nanos().div_ceil(1_000_000)).unwrap_or(i32::MAX)
+    });
+
+    loop {
+        // SAFETY: the pointer and length describe `poll_fds`, which outlives the call
+        let result = unsafe { libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as _, timeout) };
+        if result >= 0 {
+            return Ok(poll_fds.map(|poll_fd| poll_fd.revents != 0));
+        }
+
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Get the names of the services that were added, changed or removed in `new`
+fn changed_services(old: &PamConfig, new: &PamConfig) -> Vec<String> {
+    fn by_name(config: &PamConfig) -> BTreeMap<&str, &PamService> {
+        config
+            .services()
+            .iter()
+            .map(|service| (service.name(), service))
+            .collect()
+    }
+
+    let (old, new) = (by_name(old), by_name(new));
+    let names: BTreeSet<&str> = old.keys().chain(new.keys()).copied().collect();
+
+    names
+        .into_iter()
+        .filter(|name| old.get(name) != new.get(name))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Get whether any of the configuration directories exist
+fn uses_dirs(search_path: &SearchPath) -> bool {
+    search_path.dirs().iter().any(|dir| dir.is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::ffi::OsStrExt;
+    use std::sync::mpsc;
+
+    use super::*;
+
+    fn module(config: &PamConfig, name: &str) -> Option<String> {
+        let service = config
+            .services()
+            .iter()
+            .find(|service| service.name() == name)?;
+        Some(service.rules()[0].module_path()?.to_string())
+    }
+
+    #[test]
+    fn reload() {
+        let root = std::env::temp_dir().join(format!("pamela-watch-{}", std::process::id()));
+        let etc = root.join("pam.d");
+        fs::create_dir_all(&etc).unwrap();
+        fs::write(etc.join("login"), "auth required pam_env.so\n").unwrap();
+        fs::write(etc.join("sshd"), "auth required pam_unix.so\n").unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let search_path = SearchPath::new([etc.clone()], root.join("pam.conf"));
+        let watcher = ConfigWatcher::new(search_path, Duration::from_millis(50), move |event| {
+            let _ = sender.send(event);
+        })
+        .unwrap();
+
+        let before = watcher.snapshot();
+        assert_eq!(module(&before, "login").as_deref(), Some("pam_env.so"));
+
+        // A burst of writes gives a single reload
+        for module in ["pam_deny.so", "pam_permit.so", "pam_faillock.so"] {
+            fs::write(etc.join("login"), format!("auth required {module}\n")).unwrap();
+        }
+        fs::write(etc.join("su"), "auth sufficient pam_rootok.so\n").unwrap();
+        let event = receiver.recv_timeout(Duration::from_secs(5));
+
+        // A syntax error keeps the previous version of the service
+        fs::write(etc.join("sshd"), "auth requird pam_unix.so\n").unwrap();
+        let failure = receiver.recv_timeout(Duration::from_secs(5));
+        let after = watcher.snapshot();
+
+        drop(watcher);
+        fs::remove_dir_all(&root).unwrap();
+
+        let Ok(ReloadEvent::Reloaded(names)) = event else {
+            panic!("Expected a reload, got {event:?}");
+        };
+        assert_eq!(names, ["login", "su"]);
+        assert!(matches!(
+            failure,
+            Ok(ReloadEvent::Failed(PamConfigError::InFile(path, _))) if path == etc.join("sshd")
+        ));
+
+        assert_eq!(module(&before, "login").as_deref(), Some("pam_env.so"));
+        assert_eq!(module(&after, "login").as_deref(), Some("pam_faillock.so"));
+        assert_eq!(module(&after, "su").as_deref(), Some("pam_rootok.so"));
+        assert_eq!(module(&after, "sshd").as_deref(), Some("pam_unix.so"));
+    }
+
+    #[test]
+    fn packed_file() {
+        let root = std::env::temp_dir().join(format!("pamela-watch-packed-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let pam_conf = "login auth required pam_env.so\nsshd auth required pam_unix.so\n";
+        fs::write(root.join("pam.conf"), pam_conf).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let search_path = SearchPath::new([root.join("pam.d")], root.join("pam.conf"));
+        let watcher = ConfigWatcher::new(search_path, Duration::from_millis(50), move |event| {
+            let _ = sender.send(event);
+        })
+        .unwrap();
+
+        fs::write(root.join("unrelated"), "").unwrap();
+        // The unchanged `login` is not reported
+        let pam_conf = "login auth required pam_env.so\nsu auth sufficient pam_rootok.so\n";
+        fs::write(root.join("pam.conf"), pam_conf).unwrap();
+        let event = receiver.recv_timeout(Duration::from_secs(5));
+        let after = watcher.snapshot();
+
+        drop(watcher);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(matches!(event, Ok(ReloadEvent::Reloaded(names)) if names == ["sshd", "su"]));
+        assert_eq!(module(&after, "su").as_deref(), Some("pam_rootok.so"));
+        assert_eq!(module(&after, "sshd"), None);
+    }
+
+    #[test]
+    fn overflow() {
+        let root =
+            std::env::temp_dir().join(format!("pamela-watch-overflow-{}", std::process::id()));
+        let etc = root.join("pam.d");
+        fs::create_dir_all(&etc).unwrap();
+        fs::write(etc.join("login"), "auth required pam_env.so\n").unwrap();
+
+        let search_path = SearchPath::new([etc.clone()], root.join("pam.conf"));
+        let mut events = Vec::new();
+        let mut reloader = Reloader {
+            inotify: Inotify::init().unwrap(),
+            watches: HashMap::new(),
+            uses_dirs: true,
+            config: Arc::new(RwLock::new(Arc::new(
+                PamConfig::from_search_path(&search_path).unwrap(),
+            ))),
+            search_path,
+            debounce: Duration::ZERO,
+            report: |event| events.push(event),
+        };
+
+        // Events were lost, so every service is read again. A broken service keeps its previous
+        // version and a file that is not a service does not stop the others from being read.
+        fs::write(etc.join("su"), "auth sufficient pam_rootok.so\n").unwrap();
+        fs::write(etc.join("login"), "auth requird pam_unix.so\n").unwrap();
+        let odd = etc.join(OsStr::from_bytes(b"login\xff"));
+        fs::write(&odd, "auth required pam_deny.so\n").unwrap();
+        let mut changes = Changes::default();
+        let watch = reloader
+            .inotify
+            .watches()
+            .add(&etc, WatchMask::CREATE)
+            .unwrap();
+        assert!(reloader.record(&watch, EventMask::Q_OVERFLOW, None, &mut changes));
+        reloader.reload(changes);
+        let after = reloader.config.read().unwrap().clone();
+
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(matches!(
+            &events[..],
+            [
+                ReloadEvent::Failed(PamConfigError::InFile(odd_path, _)),
+                ReloadEvent::Failed(PamConfigError::InFile(login_path, _)),
+                ReloadEvent::Reloaded(names),
+            ] if *odd_path == odd && *login_path == etc.join("login") && names == &["su"]
+        ));
+        assert_eq!(module(&after, "login").as_deref(), Some("pam_env.so"));
+        assert_eq!(module(&after, "su").as_deref(), Some("pam_rootok.so"));
+    }
+}
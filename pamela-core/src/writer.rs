@@ -0,0 +1,268 @@
+//! Writing [`PamRule`]s, [`PamService`]s and [`PamConfig`]s back to their textual syntax
+
+use std::borrow::Cow;
+use std::fmt::Display;
+
+use crate::parsing::escape_string;
+use crate::{PamConfig, PamRule, PamService, RuleContent, INCLUDE_DIRECTIVE};
+
+/// Writer for the packed and separated configuration formats
+///
+/// Everything that is written by a [`PamWriter`] is parsed back into the same rules by
+/// [`PamService::from_str`] and [`PamConfig::from_str`]. The only exception is a module argument
+/// that ends with a backslash, which cannot be written faithfully and is therefore rejected by the
+/// [`PamRuleBuilder`](crate::PamRuleBuilder) and the [`ServiceEditor`](crate::ServiceEditor).
+///
+/// # Examples
+///
+/// ```
+/// use pamela::{PamService, PamWriter};
+///
+/// let rules = PamService::from_str("auth required pam_env.so\n-auth optional pam_gnome_keyring.so")?;
+/// let service = PamService::new("login", rules);
+///
+/// let written = PamWriter::new().align(true).write_service(&service);
+/// assert_eq!(
+///     written,
+///     "auth  required pam_env.so\n-auth optional pam_gnome_keyring.so\n"
+/// );
+/// # Ok::<(), pamela::PamConfigSyntaxError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PamWriter {
+    align: bool,
+    header: bool,
+}
+
+impl PamWriter {
+    /// Create a [`PamWriter`] that separates columns with a single space and writes no header
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether to pad the columns so that they line up across rules
+    pub fn align(mut self, align: bool) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Set whether to start service files with the `#%PAM-1.0` header
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Write a [`PamService`] in the separated configuration format
+    ///
+    /// This is the format used for the files in `/etc/pam.d`.
+    pub fn write_service(&self, service: &PamService) -> String {
+        self.write_rules(service.rules())
+    }
+
+    /// Write a list of [`PamRule`]s in the separated configuration format
+    pub fn write_rules(&self, rules: &[PamRule]) -> String {
+        let mut output = String::new();
+
+        if self.header {
+            output.push_str("#%PAM-1.0\n");
+        }
+
+        let lines: Vec<Vec<Cow<str>>> = rules.iter().map(rule_columns).collect();
+        self.write_lines(&mut output, &lines);
+
+        output
+    }
+
+    /// Write a [`PamConfig`] in the packed configuration format
+    ///
+    /// This is the format used for the `/etc/pam.conf` file.
+    pub fn write_config(&self, config: &PamConfig) -> String {
+        let mut output = String::new();
+
+        let lines: Vec<Vec<Cow<str>>> = config
+            .services()
+            .iter()
+            .flat_map(|service| {
+                service.rules().iter().map(|rule| {
+                    let mut columns = vec![escape_string(service.name())];
+                    columns.extend(rule_columns(rule));
+                    columns
+                })
+            })
+            .collect();
+        self.write_lines(&mut output, &lines);
+
+        output
+    }
+
+    fn write_lines(&self, output: &mut String, lines: &[Vec<Cow<str>>]) {
+        // The module arguments are never padded, so they are not part of the widths
+        let mut widths = Vec::new();
+        if self.align {
+            for line in lines {
+                let columns = line.len().saturating_sub(1);
+                widths.resize(widths.len().max(columns), 0);
+
+                for (width, column) in widths.iter_mut().zip(&line[..columns]) {
+                    *width = (*width).max(column.chars().count());
+                }
+            }
+        }
+
+        for line in lines {
+            let Some((last, columns)) = line.split_last() else {
+                continue;
+            };
+
+            for (i, column) in columns.iter().enumerate() {
+                output.push_str(column);
+
+                let width = widths.get(i).copied().unwrap_or(0);
+                let padding = width.saturating_sub(column.chars().count()) + 1;
+                output.extend(std::iter::repeat_n(' ', padding));
+            }
+
+            output.push_str(last);
+            output.push('\n');
+        }
+    }
+}
+
+/// Split a [`PamRule`] into its domain, control, module path and module arguments columns
+pub(crate) fn rule_columns(rule: &PamRule) -> Vec<Cow<'_, str>> {
+    // A directive is kept in a single column, so that it does not influence the alignment
+    let Some(domain) = rule.domain() else {
+        let service = rule.included_service().unwrap_or_default();
+        return vec![Cow::Owned(format!("{} {}", INCLUDE_DIRECTIVE, service))];
+    };
+
+    let domain = if rule.is_logging_enabled() {
+        domain.to_string()
+    } else {
+        format!("-{}", domain)
+    };
+
+    match rule.content() {
+        RuleContent::ServiceInclusion { method, service } => vec![
+            Cow::Owned(domain),
+            Cow::Owned(method.to_string()),
+            Cow::Borrowed(service.as_str()),
+        ],
+        RuleContent::Entry {
+            control,
+            module_path,
+            module_arguments,
+        } => {
+            let mut columns = vec![
+                Cow::Owned(domain),
+                Cow::Owned(control.to_string()),
+                Cow::Owned(module_path.to_string()),
+            ];
+
+            if !module_arguments.is_empty() {
+                let arguments: Vec<String> = module_arguments
+                    .iter()
+                    .map(|argument| argument.to_string())
+                    .collect();
+                columns.push(Cow::Owned(arguments.join(" ")));
+            }
+
+            columns
+        }
+    }
+}
+
+impl Display for PamRule {
+    /// Write the rule in the separated configuration format without a trailing line feed
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let columns = rule_columns(self);
+        f.write_str(&columns.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const SYSTEM_AUTH: &str = r#"
+auth       required                    pam_faillock.so      preauth
+-auth      [success=2 default=ignore]  pam_systemd_home.so
+auth       [success=1 default=bad]     pam_unix.so          try_first_pass nullok
+auth       optional                    pam_exec.so          [cmd=/bin/echo a\]b] quiet
+@include   common-session
+session    required                    pam_unix.so
+"#;
+
+    fn assert_same_rules(left: &[PamRule], right: &[PamRule]) {
+        assert_eq!(left.len(), right.len());
+        for (left, right) in left.iter().zip(right) {
+            assert_eq!(left.to_string(), right.to_string());
+        }
+    }
+
+    #[test]
+    fn rule() {
+        let rules = PamService::from_str(SYSTEM_AUTH).unwrap();
+
+        assert_eq!(
+            rules[1].to_string(),
+            "-auth [success=2 default=ignore] pam_systemd_home.so"
+        );
+        assert_eq!(
+            rules[3].to_string(),
+            "auth optional pam_exec.so [cmd=/bin/echo a\\]b] quiet"
+        );
+    }
+
+    #[test]
+    fn separated() {
+        let rules = PamService::from_str(SYSTEM_AUTH).unwrap();
+        let service = PamService::new("system-auth", rules);
+
+        let written = PamWriter::new().header(true).write_service(&service);
+        assert!(written.starts_with("#%PAM-1.0\nauth required pam_faillock.so preauth\n"));
+        assert_same_rules(&PamService::from_str(&written).unwrap(), service.rules());
+
+        let written = PamWriter::new().align(true).write_service(&service);
+        assert_eq!(
+            written.lines().nth(2).unwrap(),
+            "auth    [success=1 default=bad]    pam_unix.so     try_first_pass nullok"
+        );
+        assert_eq!(written.lines().nth(4).unwrap(), "@include common-session");
+        assert_eq!(
+            written.lines().nth(5).unwrap(),
+            "session required                   pam_unix.so"
+        );
+        assert_same_rules(&PamService::from_str(&written).unwrap(), service.rules());
+    }
+
+    #[test]
+    fn packed() {
+        let pam_conf = r#"
+login   auth    required    pam_env.so
+[odd service] auth  required    pam_unix.so [a b=c]
+login   -session optional  pam_motd.so  motd=/etc/motd
+"#;
+        let config = PamConfig::from_str(pam_conf).unwrap();
+
+        for writer in [PamWriter::new(), PamWriter::new().align(true)] {
+            let written = writer.write_config(&config);
+            let reparsed = PamConfig::from_str(&written).unwrap();
+
+            assert_eq!(reparsed.services().len(), config.services().len());
+            for (left, right) in reparsed.services().iter().zip(config.services()) {
+                assert_eq!(left.name(), right.name());
+                assert_same_rules(left.rules(), right.rules());
+            }
+        }
+
+        assert_eq!(
+            PamWriter::new().write_config(&config),
+            "login auth required pam_env.so\n\
+             login -session optional pam_motd.so motd=/etc/motd\n\
+             [odd service] auth required pam_unix.so [a b=c]\n"
+        );
+    }
+}
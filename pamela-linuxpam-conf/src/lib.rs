@@ -1,140 +1,160 @@
-mod control_flag;
-mod facility;
-mod module_arguments;
-mod module_path;
-mod parse;
-mod return_code;
-mod service;
+//! Linux-PAM flavoured access to the PAM configuration
+//!
+//! The model and the parser live in [`pamela`]. This crate keeps the names that Linux-PAM uses
+//! for them and iterates over rules together with the service they belong to.
 
-use std::str::FromStr;
+pub use pamela::{
+    Action, Control as ControlFlag, Domain as Facility, InclusionMethod, ModuleArgument,
+    ModulePath, PamConfigSyntaxError, PamConfigSyntaxErrorKind, PamRule, ReturnCode, RuleContent,
+    Selection, SelectionItem, Value,
+};
 
-pub use control_flag::ControlFlag;
-pub use facility::Facility;
-pub use module_arguments::ModuleArguments;
-pub use module_path::ModulePath;
-pub use parse::{RuleToken, RuleTokenContent};
-pub use service::Service;
-
-use self::parse::RuleTokenIterator;
+use pamela::{PackedRuleIterator, SeparatedRuleIterator};
 
 #[derive(Debug)]
 pub struct Rule {
-    do_log: bool,
-    service: Service,
-    facility: Facility,
-    content: RuleContent,
-}
-
-#[derive(Debug)]
-pub enum RuleContent {
-    ServiceInclusion {
-        method: InclusionMethod,
-        service: Service,
-    },
-    Entry {
-        control_flag: ControlFlag,
-        module_path: ModulePath,
-        module_arguments: ModuleArguments,
-    },
+    service: String,
+    rule: PamRule,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum InclusionMethod {
-    Include,
-    Substack,
+pub struct RuleIterator<'a> {
+    rules: Rules<'a>,
 }
 
-pub struct RuleIterator<'a> {
-    rule_token_iterator: RuleTokenIterator<'a>,
+enum Rules<'a> {
+    Packed(PackedRuleIterator<'a>),
+    Separated(&'a str, SeparatedRuleIterator<'a>),
 }
 
 impl Rule {
     pub fn do_log(&self) -> bool {
-        self.do_log
+        self.rule.is_logging_enabled()
     }
 
     pub fn service(&self) -> &str {
-        self.service.as_ref()
+        &self.service
     }
 
-    pub fn facility(&self) -> Facility {
-        self.facility
+    /// Get the facility, which is `None` for a Debian-style `@include` directive
+    pub fn facility(&self) -> Option<Facility> {
+        self.rule.domain()
     }
 
     pub fn content(&self) -> &RuleContent {
-        &self.content
+        self.rule.content()
+    }
+
+    /// Get the underlying [`PamRule`]
+    pub fn rule(&self) -> &PamRule {
+        &self.rule
     }
 }
 
 impl<'a> RuleIterator<'a> {
+    /// Iterate over the rules of a service file in `/etc/pam.d`
     pub fn with_service(source: &'a str, service: &'a str) -> Self {
         Self {
-            rule_token_iterator: RuleTokenIterator::with_service(source, service),
+            rules: Rules::Separated(service, PamRule::separated_iter(source)),
         }
     }
 
+    /// Iterate over the rules of a `/etc/pam.conf` file
     pub fn new(source: &'a str) -> Self {
         Self {
-            rule_token_iterator: RuleTokenIterator::new(source),
+            rules: Rules::Packed(PamRule::packed_iter(source)),
         }
     }
 }
 
 impl<'a> Iterator for RuleIterator<'a> {
-    type Item = Result<Rule, ()>;
+    type Item = Result<Rule, PamConfigSyntaxError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let rule_token = self.rule_token_iterator.next()?;
+        Some(match &mut self.rules {
+            Rules::Packed(rules) => rules
+                .next()?
+                .map(|(service, rule)| Rule { service, rule }),
+            Rules::Separated(service, rules) => rules.next()?.map(|rule| Rule {
+                service: service.to_string(),
+                rule,
+            }),
+        })
+    }
+}
 
-        let do_log = rule_token.do_log;
-        let service = Service::try_from(rule_token.service).unwrap();
-        let facility = Facility::from_str(&rule_token.facility).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let content = match rule_token.content {
-            RuleTokenContent::ServiceInclusion { method, service } => {
-                let service = Service::try_from(service).unwrap();
-                RuleContent::ServiceInclusion { method, service }
-            }
-            RuleTokenContent::Entry {
-                control_flag,
-                module_path,
-                module_arguments,
-            } => {
-                let control_flag = ControlFlag::from_str(&control_flag).unwrap();
-                let module_path = ModulePath::from_str(&module_path).unwrap();
-                let module_arguments = ModuleArguments::new(module_arguments);
+    #[test]
+    fn rule_iterator() {
+        macro_rules! assert_rules {
+            ($source:literal$(+ ($service:literal))?, [$(($rule_service:literal, $facility:expr, $content:pat $(if $guard:expr)?)),* $(,)?]) => {
+                #[allow(unused_variables)]
+                let rule_iterator = RuleIterator::new($source);
+                $(let rule_iterator = RuleIterator::with_service($source, $service);)?
+
+                let rules = rule_iterator.collect::<Result<Vec<Rule>, PamConfigSyntaxError>>().unwrap();
+                let mut rules = rules.iter();
+
+                $(
+                let rule = rules.next().unwrap();
+                assert_eq!(rule.service(), $rule_service);
+                assert_eq!(rule.facility(), $facility);
+                assert!(matches!(rule.content(), $content $(if $guard)?));
+                )*
+
+                assert!(rules.next().is_none());
+            };
+        }
+
+        assert_rules!("", []);
+        assert_rules!(
+            "login auth required pam_unix.so",
+            [(
+                "login",
+                Some(Facility::Auth),
                 RuleContent::Entry {
-                    control_flag,
-                    module_path,
-                    module_arguments,
+                    control: ControlFlag::Required,
+                    ..
                 }
-            }
-        };
-
-        Some(Ok(Rule {
-            do_log,
-            service,
-            facility,
-            content,
-        }))
+            )]
+        );
+        assert_rules!(
+            "login auth include common-auth",
+            [(
+                "login",
+                Some(Facility::Auth),
+                RuleContent::ServiceInclusion {
+                    method: InclusionMethod::Include,
+                    service,
+                } if service == "common-auth"
+            )]
+        );
+        assert_rules!(
+            "@include common-auth" + ("login"),
+            [(
+                "login",
+                None,
+                RuleContent::ServiceInclusion {
+                    method: InclusionMethod::IncludeDirective,
+                    service,
+                } if service == "common-auth"
+            )]
+        );
     }
-}
 
-impl FromStr for InclusionMethod {
-    type Err = ();
+    #[test]
+    fn syntax_error() {
+        let mut rule_iterator = RuleIterator::with_service("auth requird pam_unix.so", "login");
+        let error = rule_iterator.next().unwrap().unwrap_err();
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "include" => Ok(Self::Include),
-            "substack" => Ok(Self::Substack),
-            _ => Err(()),
-        }
+        assert!(matches!(
+            error.kind(),
+            PamConfigSyntaxErrorKind::WrongControl(_)
+        ));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
     macro_rules! file_test {
         ($file_content:literal, $($service:literal, )? $name:ident) => {
             #[test]
@@ -147,7 +167,7 @@ mod tests {
                 let rule_iterator = RuleIterator::with_service(file_content, $service);
                 )?
 
-                let rules = rule_iterator.collect::<Result<Vec<Rule>, ()>>().unwrap();
+                let rules = rule_iterator.collect::<Result<Vec<Rule>, PamConfigSyntaxError>>().unwrap();
 
                 eprintln!("{:?}", file_content);
 
@@ -165,4 +185,56 @@ account		required	pam_permit.so"#,
         "reboot",
         int_test_reboot
     );
+
+    file_test!(
+        r#"#%PAM-1.0
+auth        include     system-auth
+account     include     system-auth
+session     include     system-auth"#,
+        "sudo",
+        int_test_sudo_arch
+    );
+
+    file_test!(
+        r#"#%PAM-1.0
+auth        sufficient  pam_rootok.so
+auth        required    pam_unix.so
+account     required    pam_unix.so
+session     required    pam_unix.so
+password    required    pam_permit.so"#,
+        "usermod",
+        int_test_user_mod_arch
+    );
+
+    file_test!(
+        r#"#%PAM-1.0
+
+auth       required                    pam_faillock.so      preauth
+# Optionally use requisite above if you do not want to prompt for the password
+# on locked accounts.
+-auth      [success=2 default=ignore]  pam_systemd_home.so
+auth       [success=1 default=bad]     pam_unix.so          try_first_pass nullok
+auth       [default=die]               pam_faillock.so      authfail
+auth       optional                    pam_permit.so
+auth       required                    pam_env.so
+auth       required                    pam_faillock.so      authsucc
+# If you drop the above call to pam_faillock.so the lock will be done also
+# on non-consecutive authentication failures.
+
+-account   [success=1 default=ignore]  pam_systemd_home.so
+account    required                    pam_unix.so
+account    optional                    pam_permit.so
+account    required                    pam_time.so
+
+-password  [success=1 default=ignore]  pam_systemd_home.so
+password   required                    pam_unix.so          try_first_pass nullok shadow sha512
+password   optional                    pam_permit.so
+
+-session   optional                    pam_systemd_home.so
+session    required                    pam_limits.so
+session    required                    pam_unix.so
+session    optional                    pam_permit.so"#,
+        "system-auth",
+        int_test_system_auth_arch
+    );
 }